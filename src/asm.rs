@@ -0,0 +1,1189 @@
+// assembler for octo's language
+// https://github.com/JohnEarnest/Octo/blob/gh-pages/docs/Manual.md
+//
+// tokens are separated by whitespace, # starts a comment
+//
+//  : name                  label at the current address
+//  :alias name vx          another name for a register
+//  :const name value
+//  :calc name { expr }     evaluated right to left, parentheses group
+//  :macro name args { body }
+//  :org addr, :byte value, :byte { expr }, :call addr
+//  loop ... while cond ... again
+//  if cond then statement
+//  if cond begin ... else ... end
+//
+// a bare number is a byte of data, a bare name a call to that label,
+// labels can be used before they are defined wherever an address goes
+//
+// instructions the platform lacks are errors
+
+use crate::cpu::PROGRAM_START;
+use crate::disasm::{self, Syntax};
+use crate::instruction::Instruction;
+use crate::platform::Platform;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+
+// runaway recursive macros stop here
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    // loaded at 0x200
+    pub rom: Vec<u8>,
+    pub labels: BTreeMap<String, u16>,
+    pub source_map: SourceMap,
+}
+
+// the source line each byte of a program came from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    text: Vec<String>,
+    lines: BTreeMap<u16, usize>,
+}
+
+impl SourceMap {
+    // lines maps addresses to 1 based lines of text
+    pub fn new(text: &str, lines: BTreeMap<u16, usize>) -> Self {
+        Self {
+            text: text.lines().map(str::to_string).collect(),
+            lines,
+        }
+    }
+
+    // 1 based, none for addresses outside the program
+    pub fn line(&self, addr: u16) -> Option<usize> {
+        self.lines.get(&addr).copied()
+    }
+
+    // the line number and its text, trimmed
+    pub fn describe(&self, addr: u16) -> Option<String> {
+        let line = self.line(addr)?;
+        let text = self.text.get(line - 1)?;
+        Some(format!("line {}: {}", line, text.trim()))
+    }
+
+    // the first address of the first line from line on that has code, and
+    // that line
+    pub fn addr(&self, line: usize) -> Option<(u16, usize)> {
+        self.lines
+            .iter()
+            .filter(|(_, l)| **l >= line)
+            .map(|(addr, l)| (*l, *addr))
+            .min()
+            .map(|(l, addr)| (addr, l))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+pub fn assemble(source: &str, platform: Platform) -> Result<Program, AsmError> {
+    let mut assembler = Assembler::new(tokenize(source), platform);
+    while let Some(token) = assembler.tokens.pop_front() {
+        assembler.statement(&token)?;
+    }
+    let mut program = assembler.finish()?;
+    program.source_map.text = source.lines().map(str::to_string).collect();
+    Ok(program)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+impl Token {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (line, text) in source.lines().enumerate() {
+        let text = text.split('#').next().unwrap_or_default();
+        let mut start = None;
+        for (column, c) in text.char_indices().chain([(text.len(), ' ')]) {
+            match (c.is_whitespace(), start) {
+                (false, None) => start = Some(column),
+                (true, Some(from)) => {
+                    tokens.push_back(Token {
+                        text: text[from..column].to_string(),
+                        line: line + 1,
+                        column: text[..from].chars().count() + 1,
+                    });
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    tokens
+}
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FixupKind {
+    // the low 12 bits of the opcode at the address
+    Nnn,
+    // the 16 bit word at the address
+    Long,
+}
+
+struct Fixup {
+    at: usize,
+    kind: FixupKind,
+    token: Token,
+}
+
+enum Control {
+    Loop {
+        start: u16,
+        // jumps out of the loop, patched by again
+        whiles: Vec<usize>,
+        token: Token,
+    },
+    // jumps patched by else or end
+    If {
+        jump: usize,
+        token: Token,
+    },
+    Else {
+        jump: usize,
+        token: Token,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    Register(usize),
+    Byte(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Condition {
+    Equal(usize, Operand),
+    NotEqual(usize, Operand),
+    Key(usize),
+    NotKey(usize),
+}
+
+impl Condition {
+    fn negate(self) -> Self {
+        match self {
+            Condition::Equal(x, operand) => Condition::NotEqual(x, operand),
+            Condition::NotEqual(x, operand) => Condition::Equal(x, operand),
+            Condition::Key(x) => Condition::NotKey(x),
+            Condition::NotKey(x) => Condition::Key(x),
+        }
+    }
+
+    // the instruction that skips the next one when the condition holds
+    fn skip(self) -> Instruction {
+        match self {
+            Condition::Equal(x, Operand::Byte(kk)) => Instruction::SkipEqImm { x, kk },
+            Condition::Equal(x, Operand::Register(y)) => Instruction::SkipEq { x, y },
+            Condition::NotEqual(x, Operand::Byte(kk)) => Instruction::SkipNeImm { x, kk },
+            Condition::NotEqual(x, Operand::Register(y)) => Instruction::SkipNe { x, y },
+            Condition::Key(x) => Instruction::SkipKey(x),
+            Condition::NotKey(x) => Instruction::SkipNotKey(x),
+        }
+    }
+}
+
+struct Assembler {
+    platform: Platform,
+    tokens: VecDeque<Token>,
+    rom: Vec<u8>,
+    // address of the next byte emitted
+    here: usize,
+    labels: BTreeMap<String, u16>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, usize>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    control: Vec<Control>,
+    expansions: usize,
+    // source line of every byte emitted
+    lines: BTreeMap<u16, usize>,
+}
+
+impl Assembler {
+    fn new(tokens: VecDeque<Token>, platform: Platform) -> Self {
+        Self {
+            platform,
+            tokens,
+            rom: Vec::new(),
+            here: PROGRAM_START,
+            labels: BTreeMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            control: Vec::new(),
+            expansions: 0,
+            lines: BTreeMap::new(),
+        }
+    }
+
+    fn finish(mut self) -> Result<Program, AsmError> {
+        if let Some(control) = self.control.pop() {
+            return Err(match control {
+                Control::Loop { token, .. } => token.error("loop without again"),
+                Control::If { token, .. } | Control::Else { token, .. } => {
+                    token.error("begin without end")
+                }
+            });
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let target = match self.labels.get(&fixup.token.text) {
+                Some(target) => *target,
+                None => {
+                    return Err(fixup
+                        .token
+                        .error(format!("undefined label {}", fixup.token.text)))
+                }
+            };
+            let offset = fixup.at - PROGRAM_START;
+            let word = match fixup.kind {
+                FixupKind::Nnn if target > 0xfff => {
+                    return Err(fixup.token.error(format!(
+                        "{} is at {:#06x}, past the 12 bit address range",
+                        fixup.token.text, target
+                    )))
+                }
+                FixupKind::Nnn => (self.rom[offset] as u16) << 8 | target,
+                FixupKind::Long => target,
+            };
+            self.rom[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+        }
+        Ok(Program {
+            rom: self.rom,
+            labels: self.labels,
+            source_map: SourceMap {
+                text: Vec::new(),
+                lines: self.lines,
+            },
+        })
+    }
+
+    fn next(&mut self, after: &Token) -> Result<Token, AsmError> {
+        self.tokens
+            .pop_front()
+            .ok_or_else(|| after.error(format!("unexpected end of file after {}", after.text)))
+    }
+
+    fn expect(&mut self, after: &Token, text: &str) -> Result<Token, AsmError> {
+        let token = self.next(after)?;
+        if token.text != text {
+            return Err(token.error(format!("expected {}, found {}", text, token.text)));
+        }
+        Ok(token)
+    }
+
+    fn name(&mut self, after: &Token) -> Result<Token, AsmError> {
+        let token = self.next(after)?;
+        if !is_name(&token.text) || register_number(&token.text).is_some() {
+            return Err(token.error(format!("{} is not a valid name", token.text)));
+        }
+        Ok(token)
+    }
+
+    fn emit_byte(&mut self, token: &Token, byte: u8) -> Result<(), AsmError> {
+        if self.here >= self.platform.ram_size() {
+            return Err(token.error("the program does not fit in ram"));
+        }
+        let offset = self.here - PROGRAM_START;
+        if offset >= self.rom.len() {
+            self.rom.resize(offset + 1, 0x0);
+        }
+        self.rom[offset] = byte;
+        self.lines.insert(self.here as u16, token.line);
+        self.here += 1;
+        Ok(())
+    }
+
+    fn emit(&mut self, token: &Token, instruction: Instruction) -> Result<(), AsmError> {
+        if !instruction.supported_on(self.platform) {
+            let mnemonic =
+                disasm::format(instruction, Syntax::Octo, &|nnn| format!("0x{:03x}", nnn));
+            return Err(token.error(format!(
+                "{} is not available on {}",
+                mnemonic, self.platform
+            )));
+        }
+        for byte in instruction.encode().to_be_bytes() {
+            self.emit_byte(token, byte)?;
+        }
+        Ok(())
+    }
+
+    // an instruction with a 12 bit address, a label defined later is patched in at the end
+    fn emit_addressed(
+        &mut self,
+        token: &Token,
+        target: &Token,
+        instruction: fn(u16) -> Instruction,
+    ) -> Result<(), AsmError> {
+        let at = self.here;
+        match self.known_value(target)? {
+            Some(value) if !(0..=0xfff).contains(&value) => {
+                Err(target.error(format!("{} is past the 12 bit address range", target.text)))
+            }
+            Some(value) => self.emit(token, instruction(value as u16)),
+            None => {
+                self.emit(token, instruction(0x0))?;
+                self.fixups.push(Fixup {
+                    at,
+                    kind: FixupKind::Nnn,
+                    token: target.clone(),
+                });
+                Ok(())
+            }
+        }
+    }
+
+    // a jump to fill in once the target is known
+    fn emit_placeholder_jump(&mut self, token: &Token) -> Result<usize, AsmError> {
+        let at = self.here;
+        self.emit(token, Instruction::Jump(0x0))?;
+        Ok(at)
+    }
+
+    fn patch_jump(&mut self, at: usize, target: usize) {
+        let offset = at - PROGRAM_START;
+        let word = Instruction::Jump(target as u16).encode();
+        self.rom[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+    }
+
+    fn register(&self, token: &Token) -> Result<usize, AsmError> {
+        self.aliases
+            .get(&token.text)
+            .copied()
+            .or_else(|| register_number(&token.text))
+            .ok_or_else(|| token.error(format!("expected a register, found {}", token.text)))
+    }
+
+    fn is_register(&self, token: &Token) -> bool {
+        self.aliases.contains_key(&token.text) || register_number(&token.text).is_some()
+    }
+
+    // a number, constant or label defined already, none for names not known yet
+    fn known_value(&self, token: &Token) -> Result<Option<i64>, AsmError> {
+        if let Some(number) = parse_number(&token.text) {
+            return Ok(Some(number));
+        }
+        if let Some(value) = self.constants.get(&token.text) {
+            return Ok(Some(value.floor() as i64));
+        }
+        if let Some(addr) = self.labels.get(&token.text) {
+            return Ok(Some(*addr as i64));
+        }
+        if token
+            .text
+            .starts_with(|c: char| c.is_ascii_digit() || c == '-')
+        {
+            return Err(token.error(format!("{} is not a number", token.text)));
+        }
+        Ok(None)
+    }
+
+    fn value(&self, token: &Token) -> Result<i64, AsmError> {
+        self.known_value(token)?
+            .ok_or_else(|| token.error(format!("undefined name {}", token.text)))
+    }
+
+    fn byte(&self, token: &Token) -> Result<u8, AsmError> {
+        let value = self.value(token)?;
+        if !(-128..=255).contains(&value) {
+            return Err(token.error(format!("{} does not fit in a byte", token.text)));
+        }
+        Ok(value as u8)
+    }
+
+    fn nibble(&self, token: &Token) -> Result<u8, AsmError> {
+        let value = self.value(token)?;
+        if !(0..=15).contains(&value) {
+            return Err(token.error(format!("{} does not fit in 4 bits", token.text)));
+        }
+        Ok(value as u8)
+    }
+
+    fn statement(&mut self, token: &Token) -> Result<(), AsmError> {
+        use Instruction::*;
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name(token)?;
+                if self.labels.contains_key(&name.text) {
+                    return Err(name.error(format!("label {} is already defined", name.text)));
+                }
+                self.labels.insert(name.text, self.here as u16);
+            }
+            ":alias" => {
+                let name = self.name(token)?;
+                let register = self.next(&name)?;
+                let register = self.register(&register)?;
+                self.aliases.insert(name.text, register);
+            }
+            ":const" => {
+                let name = self.name(token)?;
+                let value = self.next(&name)?;
+                let value = self.value(&value)?;
+                self.constants.insert(name.text, value as f64);
+            }
+            ":calc" => {
+                let name = self.name(token)?;
+                let value = self.calc(&name)?;
+                self.constants.insert(name.text, value);
+            }
+            ":macro" => self.define_macro(token)?,
+            ":org" => {
+                let addr = self.next(token)?;
+                let value = self.value(&addr)?;
+                if !(PROGRAM_START as i64..self.platform.ram_size() as i64).contains(&value) {
+                    return Err(addr.error(format!("{} is outside the program area", addr.text)));
+                }
+                self.here = value as usize;
+            }
+            ":byte" => {
+                let value = match self.tokens.front() {
+                    Some(next) if next.text == "{" => self.calc(token)?.floor() as i64,
+                    _ => {
+                        let value = self.next(token)?;
+                        self.byte(&value)? as i64
+                    }
+                };
+                self.emit_byte(token, value as u8)?;
+            }
+            ":call" => {
+                let target = self.next(token)?;
+                self.emit_addressed(token, &target, Call)?;
+            }
+            "loop" => self.control.push(Control::Loop {
+                start: self.here as u16,
+                whiles: Vec::new(),
+                token: token.clone(),
+            }),
+            "while" => {
+                if !self
+                    .control
+                    .iter()
+                    .any(|control| matches!(control, Control::Loop { .. }))
+                {
+                    return Err(token.error("while outside of a loop"));
+                }
+                let condition = self.condition(token)?;
+                self.emit(token, condition.skip())?;
+                let jump = self.emit_placeholder_jump(token)?;
+                if let Some(Control::Loop { whiles, .. }) = self
+                    .control
+                    .iter_mut()
+                    .rev()
+                    .find(|control| matches!(control, Control::Loop { .. }))
+                {
+                    whiles.push(jump);
+                }
+            }
+            "again" => match self.control.pop() {
+                Some(Control::Loop { start, whiles, .. }) => {
+                    self.emit(token, Jump(start))?;
+                    for jump in whiles {
+                        self.patch_jump(jump, self.here);
+                    }
+                }
+                _ => return Err(token.error("again without loop")),
+            },
+            "if" => {
+                let condition = self.condition(token)?;
+                let form = self.next(token)?;
+                match form.text.as_str() {
+                    "then" => self.emit(token, condition.negate().skip())?,
+                    "begin" => {
+                        self.emit(token, condition.skip())?;
+                        let jump = self.emit_placeholder_jump(token)?;
+                        self.control.push(Control::If {
+                            jump,
+                            token: token.clone(),
+                        });
+                    }
+                    _ => {
+                        return Err(
+                            form.error(format!("expected then or begin, found {}", form.text))
+                        )
+                    }
+                }
+            }
+            "else" => match self.control.pop() {
+                Some(Control::If { jump, .. }) => {
+                    let end = self.emit_placeholder_jump(token)?;
+                    self.patch_jump(jump, self.here);
+                    self.control.push(Control::Else {
+                        jump: end,
+                        token: token.clone(),
+                    });
+                }
+                _ => return Err(token.error("else without if ... begin")),
+            },
+            "end" => match self.control.pop() {
+                Some(Control::If { jump, .. }) | Some(Control::Else { jump, .. }) => {
+                    self.patch_jump(jump, self.here)
+                }
+                _ => return Err(token.error("end without if ... begin")),
+            },
+            "return" | ";" => self.emit(token, Ret)?,
+            "clear" => self.emit(token, Cls)?,
+            "exit" => self.emit(token, Exit)?,
+            "lores" => self.emit(token, Low)?,
+            "hires" => self.emit(token, High)?,
+            "scroll-left" => self.emit(token, ScrollLeft)?,
+            "scroll-right" => self.emit(token, ScrollRight)?,
+            "audio" => self.emit(token, Audio)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let n = self.next(token)?;
+                let n = self.nibble(&n)?;
+                let instruction = match token.text.as_str() {
+                    "scroll-down" => ScrollDown(n),
+                    "scroll-up" => ScrollUp(n),
+                    _ => Plane(n),
+                };
+                self.emit(token, instruction)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let x = self.next(token)?;
+                let x = self.register(&x)?;
+                let instruction = match token.text.as_str() {
+                    "bcd" => Bcd(x),
+                    "saveflags" => SaveFlags(x),
+                    _ => LoadFlags(x),
+                };
+                self.emit(token, instruction)?;
+            }
+            "save" | "load" => {
+                let x = self.next(token)?;
+                let x = self.register(&x)?;
+                let instruction = match self.tokens.front() {
+                    Some(next) if next.text == "-" => {
+                        let dash = self.next(token)?;
+                        let y = self.next(&dash)?;
+                        let y = self.register(&y)?;
+                        match token.text.as_str() {
+                            "save" => StoreRange { x, y },
+                            _ => LoadRange { x, y },
+                        }
+                    }
+                    _ => match token.text.as_str() {
+                        "save" => Store(x),
+                        _ => Load(x),
+                    },
+                };
+                self.emit(token, instruction)?;
+            }
+            "sprite" => {
+                let x = self.next(token)?;
+                let x = self.register(&x)?;
+                let y = self.next(token)?;
+                let y = self.register(&y)?;
+                let n = self.next(token)?;
+                let n = self.nibble(&n)?;
+                self.emit(token, Draw { x, y, n })?;
+            }
+            "jump" | "jump0" => {
+                let target = self.next(token)?;
+                let instruction = match token.text.as_str() {
+                    "jump" => Jump,
+                    _ => JumpV0,
+                };
+                self.emit_addressed(token, &target, instruction)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                let op = self.expect(token, ":=")?;
+                let x = self.next(&op)?;
+                let x = self.register(&x)?;
+                let instruction = match token.text.as_str() {
+                    "delay" => SetDelay(x),
+                    "buzzer" => SetSound(x),
+                    _ => Pitch(x),
+                };
+                self.emit(token, instruction)?;
+            }
+            "i" => self.assign_i(token)?,
+            _ if self.is_register(token) => self.assign_register(token)?,
+            name if self.macros.contains_key(name) => self.expand(token)?,
+            // data, sprites mostly
+            text if text.starts_with(|c: char| c.is_ascii_digit() || c == '-')
+                || self.constants.contains_key(text) =>
+            {
+                let byte = self.byte(token)?;
+                self.emit_byte(token, byte)?;
+            }
+            text if text.starts_with(':') => {
+                return Err(token.error(format!("unknown directive {}", text)))
+            }
+            // a bare name calls the label
+            text if is_name(text) => self.emit_addressed(token, token, Call)?,
+            text => return Err(token.error(format!("unexpected {}", text))),
+        }
+        Ok(())
+    }
+
+    fn assign_i(&mut self, token: &Token) -> Result<(), AsmError> {
+        let op = self.next(token)?;
+        match op.text.as_str() {
+            ":=" => {
+                let value = self.next(&op)?;
+                match value.text.as_str() {
+                    "hex" | "bighex" => {
+                        let x = self.next(&value)?;
+                        let x = self.register(&x)?;
+                        let instruction = match value.text.as_str() {
+                            "hex" => Instruction::Font(x),
+                            _ => Instruction::BigFont(x),
+                        };
+                        self.emit(token, instruction)
+                    }
+                    "long" => {
+                        let target = self.next(&value)?;
+                        let at = self.here + 2;
+                        self.emit(token, Instruction::LoadILong)?;
+                        let addr = match self.known_value(&target)? {
+                            Some(addr) if !(0..=0xffff).contains(&addr) => {
+                                return Err(target
+                                    .error(format!("{} does not fit in 16 bits", target.text)))
+                            }
+                            Some(addr) => addr as u16,
+                            None => {
+                                self.fixups.push(Fixup {
+                                    at,
+                                    kind: FixupKind::Long,
+                                    token: target.clone(),
+                                });
+                                0x0
+                            }
+                        };
+                        for byte in addr.to_be_bytes() {
+                            self.emit_byte(token, byte)?;
+                        }
+                        Ok(())
+                    }
+                    _ => self.emit_addressed(token, &value, Instruction::LoadI),
+                }
+            }
+            "+=" => {
+                let x = self.next(&op)?;
+                let x = self.register(&x)?;
+                self.emit(token, Instruction::AddI(x))
+            }
+            _ => Err(op.error(format!("expected := or += after i, found {}", op.text))),
+        }
+    }
+
+    fn assign_register(&mut self, token: &Token) -> Result<(), AsmError> {
+        use Instruction::*;
+        let x = self.register(token)?;
+        let op = self.next(token)?;
+        let rhs = self.next(&op)?;
+        let y = if self.is_register(&rhs) {
+            Some(self.register(&rhs)?)
+        } else {
+            None
+        };
+        let instruction = match (op.text.as_str(), y) {
+            (":=", Some(y)) => Move { x, y },
+            (":=", None) => match rhs.text.as_str() {
+                "random" => {
+                    let mask = self.next(&rhs)?;
+                    Random {
+                        x,
+                        kk: self.byte(&mask)?,
+                    }
+                }
+                "key" => WaitKey(x),
+                "delay" => LoadDelay(x),
+                _ => LoadImm {
+                    x,
+                    kk: self.byte(&rhs)?,
+                },
+            },
+            ("+=", Some(y)) => Add { x, y },
+            ("+=", None) => AddImm {
+                x,
+                kk: self.byte(&rhs)?,
+            },
+            ("-=", Some(y)) => Sub { x, y },
+            ("-=", None) => AddImm {
+                x,
+                kk: self.byte(&rhs)?.wrapping_neg(),
+            },
+            ("=-", Some(y)) => SubN { x, y },
+            ("|=", Some(y)) => Or { x, y },
+            ("&=", Some(y)) => And { x, y },
+            ("^=", Some(y)) => Xor { x, y },
+            (">>=", Some(y)) => ShiftRight { x, y },
+            ("<<=", Some(y)) => ShiftLeft { x, y },
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(rhs.error(format!("expected a register, found {}", rhs.text)))
+            }
+            _ => return Err(op.error(format!("unknown operator {}", op.text))),
+        };
+        self.emit(token, instruction)
+    }
+
+    // comparisons other than equality go through vf first
+    fn condition(&mut self, token: &Token) -> Result<Condition, AsmError> {
+        let left = self.next(token)?;
+        let x = self.register(&left)?;
+        let op = self.next(&left)?;
+        match op.text.as_str() {
+            "key" => return Ok(Condition::Key(x)),
+            "-key" => return Ok(Condition::NotKey(x)),
+            _ => {}
+        }
+        let right = self.next(&op)?;
+        let operand = if self.is_register(&right) {
+            Operand::Register(self.register(&right)?)
+        } else {
+            Operand::Byte(self.byte(&right)?)
+        };
+        let condition = match op.text.as_str() {
+            "==" => Condition::Equal(x, operand),
+            "!=" => Condition::NotEqual(x, operand),
+            "<" | ">=" | ">" | "<=" => {
+                // vf is 1 when there is no borrow, so x >= y or y >= x
+                let (first, second) = match op.text.as_str() {
+                    "<" | ">=" => (Operand::Register(x), operand),
+                    _ => (operand, Operand::Register(x)),
+                };
+                match (first, second) {
+                    (Operand::Register(a), Operand::Register(b)) => {
+                        self.emit(token, Instruction::Move { x: 0xf, y: a })?;
+                        self.emit(token, Instruction::Sub { x: 0xf, y: b })?;
+                    }
+                    (Operand::Register(a), Operand::Byte(kk)) => {
+                        self.emit(token, Instruction::LoadImm { x: 0xf, kk })?;
+                        self.emit(token, Instruction::SubN { x: 0xf, y: a })?;
+                    }
+                    (Operand::Byte(kk), Operand::Register(b)) => {
+                        self.emit(token, Instruction::LoadImm { x: 0xf, kk })?;
+                        self.emit(token, Instruction::Sub { x: 0xf, y: b })?;
+                    }
+                    (Operand::Byte(_), Operand::Byte(_)) => unreachable!(),
+                }
+                match op.text.as_str() {
+                    "<" | ">" => Condition::Equal(0xf, Operand::Byte(0)),
+                    _ => Condition::NotEqual(0xf, Operand::Byte(0)),
+                }
+            }
+            _ => return Err(op.error(format!("unknown comparison {}", op.text))),
+        };
+        Ok(condition)
+    }
+
+    fn define_macro(&mut self, token: &Token) -> Result<(), AsmError> {
+        let name = self.name(token)?;
+        let mut args = Vec::new();
+        loop {
+            let arg = self.next(&name)?;
+            if arg.text == "{" {
+                break;
+            }
+            args.push(arg.text);
+        }
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let next = self.next(&name)?;
+            match next.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(next);
+        }
+        self.macros.insert(name.text, Macro { args, body });
+        Ok(())
+    }
+
+    fn expand(&mut self, token: &Token) -> Result<(), AsmError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(token.error(format!("{} expands forever", token.text)));
+        }
+        let count = self.macros[&token.text].args.len();
+        let mut values = Vec::new();
+        for _ in 0..count {
+            values.push(self.next(token)?.text);
+        }
+        let definition = &self.macros[&token.text];
+        let body: Vec<Token> = definition
+            .body
+            .iter()
+            .map(|body| {
+                let text = match definition.args.iter().position(|arg| *arg == body.text) {
+                    Some(n) => values[n].clone(),
+                    None => body.text.clone(),
+                };
+                Token {
+                    text,
+                    line: body.line,
+                    column: body.column,
+                }
+            })
+            .collect();
+        for token in body.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    // { expr }, after the name the value goes to
+    fn calc(&mut self, after: &Token) -> Result<f64, AsmError> {
+        let open = self.expect(after, "{")?;
+        let mut expr = Vec::new();
+        loop {
+            let next = self.next(&open)?;
+            if next.text == "}" {
+                break;
+            }
+            expr.push(next);
+        }
+        let mut at = 0;
+        let value = self.expression(&expr, &mut at, &open)?;
+        if let Some(extra) = expr.get(at) {
+            return Err(extra.error(format!("unexpected {}", extra.text)));
+        }
+        Ok(value)
+    }
+
+    // right to left, a - b - c is a - (b - c)
+    fn expression(&self, expr: &[Token], at: &mut usize, open: &Token) -> Result<f64, AsmError> {
+        let left = self.term(expr, at, open)?;
+        let Some(op) = expr.get(*at) else {
+            return Ok(left);
+        };
+        if op.text == ")" {
+            return Ok(left);
+        }
+        *at += 1;
+        let right = self.expression(expr, at, open)?;
+        let (a, b) = (left, right);
+        let value = match op.text.as_str() {
+            "+" => a + b,
+            "-" => a - b,
+            "*" => a * b,
+            "/" | "%" if b == 0.0 => return Err(op.error("division by zero")),
+            "/" => a / b,
+            "%" => a % b,
+            "&" => (a as i64 & b as i64) as f64,
+            "|" => (a as i64 | b as i64) as f64,
+            "^" => (a as i64 ^ b as i64) as f64,
+            "<<" => ((a as i64) << (b as i64 & 63)) as f64,
+            ">>" => ((a as i64) >> (b as i64 & 63)) as f64,
+            "pow" => a.powf(b),
+            "min" => a.min(b),
+            "max" => a.max(b),
+            "<" => (a < b) as i64 as f64,
+            ">" => (a > b) as i64 as f64,
+            "<=" => (a <= b) as i64 as f64,
+            ">=" => (a >= b) as i64 as f64,
+            "==" => (a == b) as i64 as f64,
+            "!=" => (a != b) as i64 as f64,
+            _ => return Err(op.error(format!("unknown operator {}", op.text))),
+        };
+        Ok(value)
+    }
+
+    fn term(&self, expr: &[Token], at: &mut usize, open: &Token) -> Result<f64, AsmError> {
+        let Some(token) = expr.get(*at) else {
+            return Err(open.error("incomplete expression"));
+        };
+        *at += 1;
+        let unary = |f: fn(f64) -> f64, at: &mut usize| -> Result<f64, AsmError> {
+            Ok(f(self.term(expr, at, open)?))
+        };
+        match token.text.as_str() {
+            "(" => {
+                let value = self.expression(expr, at, open)?;
+                match expr.get(*at) {
+                    Some(close) if close.text == ")" => {
+                        *at += 1;
+                        Ok(value)
+                    }
+                    _ => Err(token.error("( without )")),
+                }
+            }
+            "-" => unary(|v| -v, at),
+            "~" => unary(|v| !(v as i64) as f64, at),
+            "!" => unary(|v| (v == 0.0) as i64 as f64, at),
+            "floor" => unary(f64::floor, at),
+            "ceil" => unary(f64::ceil, at),
+            "abs" => unary(f64::abs, at),
+            "sqrt" => unary(f64::sqrt, at),
+            "sin" => unary(f64::sin, at),
+            "cos" => unary(f64::cos, at),
+            "log" => unary(f64::ln, at),
+            "HERE" => Ok(self.here as f64),
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            text => match self.constants.get(text) {
+                Some(value) => Ok(*value),
+                None => Ok(self.value(token)? as f64),
+            },
+        }
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+fn register_number(text: &str) -> Option<usize> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    usize::from_str_radix(digit, 16).ok()
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or(digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or(digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse::<i64>().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(source: &str) -> Vec<u8> {
+        assemble(source, Platform::XoChip).unwrap().rom
+    }
+
+    fn error(source: &str, platform: Platform) -> String {
+        assemble(source, platform).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_instructions() {
+        assert_eq!(
+            rom("clear v1 := 5 v1 += v2 i := 0x300 sprite v1 v2 5 return"),
+            [0x00, 0xe0, 0x61, 0x05, 0x81, 0x24, 0xa3, 0x00, 0xd1, 0x25, 0x00, 0xee]
+        );
+        assert_eq!(
+            rom("v3 := random 0xff vA -= 1 i := hex v3 bcd v3 save v3 load v2 - v4"),
+            [0xc3, 0xff, 0x7a, 0xff, 0xf3, 0x29, 0xf3, 0x33, 0xf3, 0x55, 0x52, 0x43]
+        );
+        // sprite data and comments
+        assert_eq!(rom("0xff 0b10000001 # a comment\n255"), [0xff, 0x81, 0xff]);
+    }
+
+    #[test]
+    fn test_labels() {
+        let program = assemble(
+            ": main\n  i := sprite\n  draw\n  jump main\n: draw\n  return\n: sprite 0x80",
+            Platform::Chip8,
+        )
+        .unwrap();
+        assert_eq!(
+            program.rom,
+            [0xa2, 0x08, 0x22, 0x06, 0x12, 0x00, 0x00, 0xee, 0x80]
+        );
+        assert_eq!(program.labels["sprite"], 0x208);
+    }
+
+    #[test]
+    fn test_directives() {
+        assert_eq!(
+            rom(":alias x v4 :const speed 3 :calc double { speed * 2 } x := double x += speed"),
+            [0x64, 0x06, 0x74, 0x03]
+        );
+        // right to left, 10 - (2 - 1)
+        assert_eq!(rom(":calc n { 10 - 2 - 1 } :byte n"), [0x09]);
+        assert_eq!(rom(":byte { ( 10 - 2 ) - 1 } :byte -1"), [0x07, 0xff]);
+        assert_eq!(rom(":org 0x204 :byte 1"), [0x00, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(
+            rom(":macro twice reg { reg += 1 reg += 1 } twice v2 twice v3"),
+            [0x72, 0x01, 0x72, 0x01, 0x73, 0x01, 0x73, 0x01]
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        // skip when the condition fails
+        assert_eq!(rom("if v1 == 5 then v2 := 1"), [0x41, 0x05, 0x62, 0x01]);
+        assert_eq!(rom("if v1 key then return"), [0xe1, 0xa1, 0x00, 0xee]);
+        assert_eq!(
+            rom("if v1 != v2 begin v3 := 1 else v3 := 2 end"),
+            [0x91, 0x20, 0x12, 0x08, 0x63, 0x01, 0x12, 0x0a, 0x63, 0x02]
+        );
+        assert_eq!(
+            rom("loop v0 += 1 while v0 != 10 again"),
+            [0x70, 0x01, 0x40, 0x0a, 0x12, 0x08, 0x12, 0x00]
+        );
+        // vf := v1, vf -= v2, vf is 0 when v1 < v2
+        assert_eq!(
+            rom("if v1 < v2 then v3 := 1"),
+            [0x8f, 0x10, 0x8f, 0x25, 0x4f, 0x00, 0x63, 0x01]
+        );
+    }
+
+    // v2 is 1 when the branch was taken
+    fn branch_taken(condition: &str, v0: u8, v1: u8) -> bool {
+        let source = format!(
+            "v0 := {} v1 := {} v2 := 0 if {} then v2 := 1 : end jump end",
+            v0, v1, condition
+        );
+        let mut cpu = crate::Cpu::with_platform(Platform::XoChip);
+        cpu.load_rom(rom(&source)).unwrap();
+        for _ in 0..10 {
+            cpu.step().unwrap();
+        }
+        cpu.registers()[2] == 1
+    }
+
+    #[test]
+    fn test_comparisons_run() {
+        for (v0, v1) in [(1, 2), (2, 1), (2, 2)] {
+            for (op, holds) in [
+                ("<", v0 < v1),
+                (">", v0 > v1),
+                ("<=", v0 <= v1),
+                (">=", v0 >= v1),
+                ("==", v0 == v1),
+                ("!=", v0 != v1),
+            ] {
+                let registers = format!("v0 {} v1", op);
+                assert_eq!(
+                    branch_taken(&registers, v0, v1),
+                    holds,
+                    "{} {} {}",
+                    v0,
+                    op,
+                    v1
+                );
+                let byte = format!("v0 {} {}", op, v1);
+                assert_eq!(branch_taken(&byte, v0, v1), holds, "{} {} {}", v0, op, v1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_platform_gating() {
+        assert_eq!(
+            error("clear\n  hires", Platform::Chip8),
+            "2:3: hires is not available on chip8"
+        );
+        assert_eq!(
+            rom("hires i := long 0x1234"),
+            [0x00, 0xff, 0xf0, 0x00, 0x12, 0x34]
+        );
+        assert!(assemble("hires scroll-down 2", Platform::SuperChip).is_ok());
+        assert_eq!(
+            error("scroll-up 2", Platform::SuperChip),
+            "1:1: scroll-up 2 is not available on schip1.1"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            error("v1 := 300", Platform::Chip8),
+            "1:7: 300 does not fit in a byte"
+        );
+        assert_eq!(
+            error("jump nowhere", Platform::Chip8),
+            "1:6: undefined label nowhere"
+        );
+        assert_eq!(
+            error("\n  loop v1 += 1", Platform::Chip8),
+            "2:3: loop without again"
+        );
+        assert_eq!(
+            error("end", Platform::Chip8),
+            "1:1: end without if ... begin"
+        );
+        assert_eq!(
+            error("v1 ** v2", Platform::Chip8),
+            "1:4: unknown operator **"
+        );
+        assert_eq!(
+            error(": v1", Platform::Chip8),
+            "1:3: v1 is not a valid name"
+        );
+        assert_eq!(
+            error(": a : a", Platform::Chip8),
+            "1:7: label a is already defined"
+        );
+        assert_eq!(
+            error(":foo", Platform::Chip8),
+            "1:1: unknown directive :foo"
+        );
+        assert_eq!(
+            error("i := 0x1000", Platform::Chip8),
+            "1:6: 0x1000 is past the 12 bit address range"
+        );
+    }
+
+    #[test]
+    fn test_disassembly_round_trips() {
+        let rom = [
+            0xa2, 0x0c, 0x22, 0x0a, 0x3a, 0x05, 0x12, 0x00, 0xf0, 0x00, 0x12, 0x34, 0x00, 0xfe,
+            0xd1, 0x2f, 0xb2, 0x00, 0x00, 0xee, 0x80, 0x81, 0x5f,
+        ];
+        let listing = disasm::Listing::new(&rom, Platform::XoChip).render(Syntax::Octo);
+        assert_eq!(assemble(&listing, Platform::XoChip).unwrap().rom, rom);
+    }
+
+    #[test]
+    fn test_source_map() {
+        let program =
+            assemble(": main\n  v1 := 5 # five\n\n  jump main\n", Platform::Chip8).unwrap();
+        let map = &program.source_map;
+        assert_eq!(map.line(0x200), Some(2));
+        assert_eq!(map.line(0x203), Some(4));
+        assert_eq!(map.line(0x204), None);
+        assert_eq!(map.describe(0x202).unwrap(), "line 4: jump main");
+        assert_eq!(map.describe(0x200).unwrap(), "line 2: v1 := 5 # five");
+    }
+}
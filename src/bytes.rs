@@ -0,0 +1,68 @@
+// a cursor over the little endian binary formats, save states and movies
+//
+// errors name the format being read, "save state is truncated"
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    what: &'static str,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8], what: &'static str) -> Self {
+        Self { bytes, what }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err(format!("{} is truncated", self.what));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.bytes(N)?.try_into().expect("read N bytes"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn string(&mut self, len: usize) -> Result<String, String> {
+        String::from_utf8(self.bytes(len)?.to_vec())
+            .map_err(|_| format!("{} is corrupt", self.what))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader() {
+        let mut reader = Reader::new(&[0x1, 0x2, 0x3, b'o', b'k', 0xff], "test");
+        assert_eq!(reader.u8(), Ok(0x1));
+        assert_eq!(reader.u16(), Ok(0x0302));
+        assert_eq!(reader.string(2), Ok("ok".to_string()));
+        assert_eq!(reader.u16(), Err("test is truncated".to_string()));
+        assert_eq!(reader.string(1), Err("test is corrupt".to_string()));
+        assert!(reader.is_empty());
+    }
+}
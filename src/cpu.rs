@@ -1,11 +1,52 @@
 // for reference refer to
 // http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
 
-use tinyrand::{Rand, StdRand};
+use crate::error::{CpuError, StepOutcome};
+use crate::font;
+use crate::instruction::{self, Instruction};
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use crate::rng::{Rng, RngMode};
+use crate::romdb;
+
+mod savestate;
+mod watch;
+
+pub use watch::{Access, WatchHit, Watchpoint};
+
+pub const PROGRAM_START: usize = 0x200;
+pub const LORES_WIDTH: usize = 64;
+pub const LORES_HEIGHT: usize = 32;
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+// xo-chip sound, 128 one bit samples played in a loop
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub bits: [u8; 16],
+    pub pitch: u8,
+}
+
+impl AudioPattern {
+    // samples played per second, 4000 at the default pitch of 64
+    pub fn rate(&self) -> f32 {
+        4000.0 * 2f32.powf((self.pitch as f32 - 64.0) / 48.0)
+    }
+
+    // sample n of the 128, most significant bit first
+    pub fn bit(&self, n: usize) -> bool {
+        let n = n % 128;
+        self.bits[n / 8] & (0x80 >> (n % 8)) > 0
+    }
+}
 
 pub struct Cpu {
-    ram: [u8; 0xfff],
-    pub vram: [[u8; 32]; 64],
+    ram: Vec<u8>, // 4 KiB, or 64 KiB on xo-chip
+    // sized for high resolution, low resolution only uses the top left corner
+    // each pixel holds one bit per xo-chip plane, bit 0 is the first plane
+    vram: [[u8; HIRES_HEIGHT]; HIRES_WIDTH],
+    planes: u8, // planes selected for drawing
+    hires: bool,
     reg: [u8; 0x10], // registers
     i: u16,          // index register
     pc: u16,         // program counter
@@ -14,14 +55,40 @@ pub struct Cpu {
     dt: u8, // delay timer
     st: u8, // sound timer
     keypad: [u8; 0x10],
-    rand: StdRand,
+    rand: Rng,
+    seed: u64,
+    font: [u8; 80],
+    font_address: u16,
+    big_font: [u8; 160],
+    big_font_address: u16,
+    platform: Platform,
+    quirks: Quirks,
+    // set by tick_timers at the start of every frame, cleared by the next step
+    vblank: bool,
+    rpl: [u8; 0x10], // super-chip rpl user flags
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+    // sha-1 of the loaded rom, see romdb
+    rom_hash: Option<String>,
+    watchpoints: Vec<Watchpoint>,
+    watch_hits: Vec<WatchHit>,
+    // pc and opcode of the instruction running, for watch hits
+    executing: (u16, u16),
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Cpu {
     pub fn new() -> Self {
-        Self {
-            ram: [0x0; 0xfff],
-            vram: [[0x0; 32]; 64],
+        let mut cpu = Self {
+            ram: vec![0x0; Platform::default().ram_size()],
+            vram: [[0x0; HIRES_HEIGHT]; HIRES_WIDTH],
+            planes: 0x1,
+            hires: false,
             reg: [0x0; 0x10],
             i: 0x0,
             pc: 0x200, // initial start adress once ROM is loaded
@@ -30,156 +97,633 @@ impl Cpu {
             dt: 0x0,
             st: 0x0,
             keypad: [0x0; 0x10],
-            rand: StdRand::default(),
+            rand: Rng::default(),
+            seed: 0,
+            font: font::FONT,
+            font_address: font::FONT_ADDRESS,
+            big_font: font::BIG_FONT,
+            big_font_address: font::BIG_FONT_ADDRESS,
+            platform: Platform::default(),
+            quirks: Platform::default().quirks(),
+            vblank: true,
+            rpl: [0x0; 0x10],
+            pattern: None,
+            pitch: 64,
+            rom_hash: None,
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
+            executing: (0x200, 0x0),
+        };
+        cpu.install_fonts();
+        cpu
+    }
+
+    pub fn with_platform(platform: Platform) -> Self {
+        let mut cpu = Self::new();
+        cpu.platform = platform;
+        cpu.quirks = platform.quirks();
+        cpu.reset();
+        cpu
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut cpu = Self::new();
+        cpu.set_seed(seed);
+        cpu
+    }
+
+    // restart the random numbers from this seed, reset goes back to it too
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rand = Rng::with_mode(seed, self.rand.mode());
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // restarts the random numbers from the seed
+    pub fn set_rng_mode(&mut self, mode: RngMode) {
+        self.rand = Rng::with_mode(self.seed, mode);
+    }
+
+    pub fn rng_mode(&self) -> RngMode {
+        self.rand.mode()
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    // replace the quirks of the platform preset
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    // put the machine back into its power on state
+    // the platform, quirks, seed and the fonts (and their addresses) chosen by the caller
+    // are kept, the fonts are reinstalled
+    // the rpl flags survive a reset, like they did on the hp48
+    pub fn reset(&mut self) {
+        self.ram = vec![0x0; self.platform.ram_size()];
+        self.vram = [[0x0; HIRES_HEIGHT]; HIRES_WIDTH];
+        self.planes = 0x1;
+        self.hires = false;
+        self.reg = [0x0; 0x10];
+        self.i = 0x0;
+        self.pc = 0x200;
+        self.stack = [0x0; 0x10];
+        self.sp = 0x0;
+        self.dt = 0x0;
+        self.st = 0x0;
+        self.keypad = [0x0; 0x10];
+        self.rand = Rng::with_mode(self.seed, self.rand.mode());
+        self.pattern = None;
+        self.pitch = 64;
+        self.vblank = true;
+        self.rom_hash = None;
+        self.install_fonts();
+    }
+
+    pub fn load_rom(&mut self, input: Vec<u8>) -> Result<(), CpuError> {
+        let max = self.ram.len() - PROGRAM_START;
+        if input.len() > max {
+            return Err(CpuError::RomTooLarge {
+                size: input.len(),
+                max,
+            });
         }
+        self.ram[PROGRAM_START..PROGRAM_START + input.len()].copy_from_slice(&input);
+        self.rom_hash = Some(romdb::rom_hash(&input));
+        Ok(())
+    }
+
+    // lowercase hex sha-1 of the last rom loaded, None after a reset
+    pub fn rom_hash(&self) -> Option<&str> {
+        self.rom_hash.as_deref()
+    }
+
+    /* machine state */
+
+    // v0 - vf
+    pub fn registers(&self) -> &[u8; 0x10] {
+        &self.reg
+    }
+
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.reg[x & 0x0f] = value;
     }
 
-    pub fn load_rom(&mut self, input: Vec<u8>) {
-        let mut address: usize = 0x200;
-        for byte in input.iter() {
-            self.ram[address] = *byte;
-            address += 1;
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_i(&mut self, value: u16) {
+        self.i = value;
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    // the stack slot holding the most recent return address, 0 when empty
+    pub fn sp(&self) -> u8 {
+        self.sp
+    }
+
+    pub fn stack(&self) -> &[u16; 0x10] {
+        &self.stack
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    // 128 in high resolution mode, 64 otherwise
+    pub fn display_width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            LORES_WIDTH
+        }
+    }
+
+    // 64 in high resolution mode, 32 otherwise
+    pub fn display_height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            LORES_HEIGHT
+        }
+    }
+
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    // non zero when the pixel is lit, x grows to the right and y grows down
+    // bit 0 is set when lit on the first plane, bit 1 on the second xo-chip plane
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.vram[x][y]
+    }
+
+    // the xo-chip sound loaded with f002, None until a rom loads one
+    pub fn audio_pattern(&self) -> Option<AudioPattern> {
+        self.pattern.map(|bits| AudioPattern {
+            bits,
+            pitch: self.pitch,
+        })
+    }
+
+    pub fn key(&self, key: u8) -> bool {
+        self.keypad[(key & 0x0f) as usize] > 0
+    }
+
+    // press or release one of the 16 keypad keys
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        self.keypad[(key & 0x0f) as usize] = pressed as u8;
+    }
+
+    // every keypad key as one bit, bit n set when key n is down
+    pub fn keypad_mask(&self) -> u16 {
+        (0..0x10).fold(0, |mask, key| mask | ((self.key(key) as u16) << key))
+    }
+
+    pub fn set_keypad_mask(&mut self, mask: u16) {
+        for key in 0..0x10 {
+            self.set_key(key, mask & (1 << key) > 0);
         }
     }
 
-    pub fn step(&mut self) {
-        // let inst: u8 = self.ram[self.pc as usize];
-        let msb = self.ram[self.pc as usize] as u16;
-        self.pc += 1;
-        let lsb = self.ram[self.pc as usize] as u16;
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.dt = value;
+    }
+
+    // the beeper sounds for as long as this is above zero
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.st = value;
+    }
+
+    // count both timers down by one, this is to be called at 60 Hz
+    // regardless of how many instructions are being executed per second
+    pub fn tick_timers(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
+        self.vblank = true;
+    }
+
+    /* fonts */
+
+    // address of the small font, used by fx29
+    pub fn font_address(&self) -> u16 {
+        self.font_address
+    }
+
+    // address of the large super-chip font
+    pub fn big_font_address(&self) -> u16 {
+        self.big_font_address
+    }
+
+    // move the small font, the font must fit inside the interpreter area
+    pub fn set_font_address(&mut self, address: u16) -> Result<(), String> {
+        if !Self::fits_interpreter_area(address, self.font.len()) {
+            return Err(format!(
+                "font address {:#05x} does not fit below 0x200",
+                address
+            ));
+        }
+        self.clear_ram(self.font_address, self.font.len());
+        self.font_address = address;
+        self.install_fonts();
+        Ok(())
+    }
+
+    // move the large font, the font must fit inside the interpreter area
+    pub fn set_big_font_address(&mut self, address: u16) -> Result<(), String> {
+        if !Self::fits_interpreter_area(address, self.big_font.len()) {
+            return Err(format!(
+                "big font address {:#05x} does not fit below 0x200",
+                address
+            ));
+        }
+        self.clear_ram(self.big_font_address, self.big_font.len());
+        self.big_font_address = address;
+        self.install_fonts();
+        Ok(())
+    }
+
+    // replace the built-in small font, 16 glyphs of 5 bytes each
+    pub fn load_font(&mut self, font: [u8; 80]) {
+        self.font = font;
+        self.install_fonts();
+    }
+
+    // replace the built-in large font, 16 glyphs of 10 bytes each
+    pub fn load_big_font(&mut self, font: [u8; 160]) {
+        self.big_font = font;
+        self.install_fonts();
+    }
+
+    fn install_fonts(&mut self) {
+        let address = self.big_font_address as usize;
+        self.ram[address..address + self.big_font.len()].copy_from_slice(&self.big_font);
+        // the small font is written last so it wins if the two overlap
+        let address = self.font_address as usize;
+        self.ram[address..address + self.font.len()].copy_from_slice(&self.font);
+    }
+
+    fn clear_ram(&mut self, address: u16, len: usize) {
+        let address = address as usize;
+        self.ram[address..address + len].fill(0x0);
+    }
+
+    fn fits_interpreter_area(address: u16, len: usize) -> bool {
+        address as usize + len <= PROGRAM_START
+    }
+
+    pub fn step(&mut self) -> Result<StepOutcome, CpuError> {
+        let pc = self.pc;
+        let vblank = std::mem::replace(&mut self.vblank, false);
+        let msb = self.ram_byte(pc as usize)? as u16;
+        self.pc = self.pc.wrapping_add(1);
+        let lsb = self.ram_byte(self.pc as usize)? as u16;
         let mut inst: u16 = msb << 8;
         inst |= lsb;
-        self.pc += 1;
-        match inst & 0xf000 {
-            0x0000 => match inst & 0x00ff {
-                0x00e0 => self.op_00e0(inst),
-                0x00ee => self.op_00ee(inst),
-                _ => panic!("Instruction not valid: {}", inst),
-            },
-            0x1000 => self.op_1nnn(inst),
-            0x2000 => self.op_2nnn(inst),
-            0x3000 => self.op_3xkk(inst),
-            0x4000 => self.op_4xkk(inst),
-            0x5000 => self.op_5xy0(inst),
-            0x6000 => self.op_6xkk(inst),
-            0x7000 => self.op_7xkk(inst),
-            0x8000 => match inst & 0x000f {
-                0x0000 => self.op_8xy0(inst),
-                0x0001 => self.op_8xy1(inst),
-                0x0002 => self.op_8xy2(inst),
-                0x0003 => self.op_8xy3(inst),
-                0x0004 => self.op_8xy4(inst),
-                0x0005 => self.op_8xy5(inst),
-                0x0006 => self.op_8xy6(inst),
-                0x0007 => self.op_8xy7(inst),
-                0x000e => self.op_8xye(inst),
-                _ => panic!("Instruction not valid: {}", inst),
-            },
-            0x9000 => self.op_9xy0(inst),
-            0xa000 => self.op_annn(inst),
-            0xb000 => self.op_bnnn(inst),
-            0xc000 => self.op_cxkk(inst),
-            0xd000 => self.op_dxyn(inst),
-            0xe000 => match inst & 0x00ff {
-                0x009e => self.op_ex9e(inst),
-                0x00a1 => self.op_exa1(inst),
-                _ => panic!("Instruction not valid: {}", inst),
-            },
-            0xf000 => match inst & 0x00ff {
-                0x0007 => self.op_fx07(inst),
-                0x000a => self.op_fx0a(inst),
-                0x0015 => self.op_fx15(inst),
-                0x0018 => self.op_fx18(inst),
-                0x001e => self.op_fx1e(inst),
-                0x0029 => self.op_fx29(inst),
-                0x0033 => self.op_fx33(inst),
-                0x0055 => self.op_fx55(inst),
-                0x0065 => self.op_fx65(inst),
-                _ => panic!("Instruction not valid: {}", inst),
-            },
-            _ => panic!("Instruction not valid: {}", inst),
+        if !self.watchpoints.is_empty() {
+            self.executing = (pc, inst);
+            self.watch(Access::Exec, pc as usize, msb as u8, msb as u8);
+            self.watch(Access::Exec, self.pc as usize, lsb as u8, lsb as u8);
+        }
+        self.pc = self.pc.wrapping_add(1);
+        let instruction =
+            instruction::decode(inst).map_err(|opcode| CpuError::InvalidOpcode { pc, opcode })?;
+        // try again once the next frame starts, pc stays on the draw
+        if let Instruction::Draw { .. } = instruction {
+            if self.quirks.display_wait && !vblank {
+                self.pc = pc;
+                return Ok(StepOutcome::WaitingForVblank);
+            }
+        }
+        self.execute(instruction)
+    }
+
+    // the instruction in ram at addr, none when it does not decode
+    pub fn instruction_at(&self, addr: u16) -> Option<Instruction> {
+        let addr = addr as usize;
+        let opcode = (*self.ram.get(addr)? as u16) << 8 | *self.ram.get(addr + 1)? as u16;
+        instruction::decode(opcode).ok()
+    }
+
+    // run a decoded instruction, pc is expected to point past it already
+    // instructions the platform lacks are invalid
+    pub fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, CpuError> {
+        use Instruction::*;
+        if !instruction.supported_on(self.platform) {
+            return Err(CpuError::InvalidOpcode {
+                pc: self.pc.wrapping_sub(2),
+                opcode: instruction.encode(),
+            });
+        }
+        let mut outcome = StepOutcome::Executed;
+        match instruction {
+            // never supported, turned away above
+            Sys(_) => {}
+            Cls => self.op_00e0(),
+            Ret => self.op_00ee()?,
+            ScrollDown(n) => self.op_00cn(n),
+            ScrollUp(n) => self.op_00dn(n),
+            ScrollRight => self.op_00fb(),
+            ScrollLeft => self.op_00fc(),
+            Exit => outcome = self.op_00fd(),
+            Low => self.op_00fe(),
+            High => self.op_00ff(),
+            Jump(nnn) => self.op_1nnn(nnn),
+            Call(nnn) => self.op_2nnn(nnn)?,
+            SkipEqImm { x, kk } => self.op_3xkk(x, kk),
+            SkipNeImm { x, kk } => self.op_4xkk(x, kk),
+            SkipEq { x, y } => self.op_5xy0(x, y),
+            StoreRange { x, y } => self.op_5xy2(x, y)?,
+            LoadRange { x, y } => self.op_5xy3(x, y)?,
+            LoadImm { x, kk } => self.op_6xkk(x, kk),
+            AddImm { x, kk } => self.op_7xkk(x, kk),
+            Move { x, y } => self.op_8xy0(x, y),
+            Or { x, y } => self.op_8xy1(x, y),
+            And { x, y } => self.op_8xy2(x, y),
+            Xor { x, y } => self.op_8xy3(x, y),
+            Add { x, y } => self.op_8xy4(x, y),
+            Sub { x, y } => self.op_8xy5(x, y),
+            ShiftRight { x, y } => self.op_8xy6(x, y),
+            SubN { x, y } => self.op_8xy7(x, y),
+            ShiftLeft { x, y } => self.op_8xye(x, y),
+            SkipNe { x, y } => self.op_9xy0(x, y),
+            LoadI(nnn) => self.op_annn(nnn),
+            JumpV0(nnn) => self.op_bnnn(nnn),
+            Random { x, kk } => self.op_cxkk(x, kk),
+            Draw { x, y, n } => self.op_dxyn(x, y, n)?,
+            SkipKey(x) => self.op_ex9e(x),
+            SkipNotKey(x) => self.op_exa1(x),
+            LoadILong => self.op_f000()?,
+            Plane(n) => self.op_fn01(n),
+            Audio => self.op_f002()?,
+            LoadDelay(x) => self.op_fx07(x),
+            WaitKey(x) => outcome = self.op_fx0a(x),
+            SetDelay(x) => self.op_fx15(x),
+            SetSound(x) => self.op_fx18(x),
+            AddI(x) => self.op_fx1e(x),
+            Font(x) => self.op_fx29(x),
+            BigFont(x) => self.op_fx30(x),
+            Bcd(x) => self.op_fx33(x)?,
+            Pitch(x) => self.op_fx3a(x),
+            Store(x) => self.op_fx55(x)?,
+            Load(x) => self.op_fx65(x)?,
+            SaveFlags(x) => self.op_fx75(x),
+            LoadFlags(x) => self.op_fx85(x),
+        }
+        Ok(outcome)
+    }
+
+    // skip the next instruction, on xo-chip that can be the 4 byte f000 nnnn
+    fn skip(&mut self) {
+        let pc = self.pc as usize;
+        let long = self.platform.supports_xochip()
+            && self.ram.get(pc) == Some(&0xf0)
+            && self.ram.get(pc + 1) == Some(&0x00);
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    // move the selected planes by dx, dy pixels, pixels moved in from
+    // outside the display are blank
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let old = self.vram;
+        let width = self.display_width() as isize;
+        let height = self.display_height() as isize;
+        for x in 0..width {
+            for y in 0..height {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&from_x) && (0..height).contains(&from_y) {
+                    old[from_x as usize][from_y as usize]
+                } else {
+                    0x0
+                };
+                let pixel = &mut self.vram[x as usize][y as usize];
+                *pixel = (*pixel & !self.planes) | (moved & self.planes);
+            }
         }
     }
 
+    // every ram access an instruction makes goes through read_ram or
+    // write_ram so watchpoints see it
+    fn read_ram(&mut self, addr: usize) -> Result<u8, CpuError> {
+        let value = self.ram_byte(addr)?;
+        if !self.watchpoints.is_empty() {
+            self.watch(Access::Read, addr, value, value);
+        }
+        Ok(value)
+    }
+
+    fn write_ram(&mut self, addr: usize, value: u8) -> Result<(), CpuError> {
+        let old = self.ram_byte(addr)?;
+        self.ram[addr] = value;
+        if !self.watchpoints.is_empty() {
+            self.watch(Access::Write, addr, old, value);
+        }
+        Ok(())
+    }
+
+    fn ram_byte(&self, addr: usize) -> Result<u8, CpuError> {
+        self.ram
+            .get(addr)
+            .copied()
+            .ok_or(CpuError::MemoryOutOfBounds { addr })
+    }
+
     /* instructions */
 
     // 0nnn
-    pub fn _op_0nnn(&mut self, _inst: u16) {
+    pub fn _op_0nnn(&mut self) {
         // do nothing
     }
 
     // cls - clear the display
+    // only the selected planes are cleared
     // 00e0
-    fn op_00e0(&mut self, _inst: u16) {
-        self.vram = [[0x0; 32]; 64];
+    fn op_00e0(&mut self) {
+        for column in self.vram.iter_mut() {
+            for pixel in column.iter_mut() {
+                *pixel &= !self.planes;
+            }
+        }
+    }
+
+    // scd - scroll the display down n pixels
+    // 00cn
+    fn op_00cn(&mut self, n: u8) {
+        self.scroll(0, n as isize);
+    }
+
+    // scu - scroll the display up n pixels
+    // 00dn
+    fn op_00dn(&mut self, n: u8) {
+        self.scroll(0, -(n as isize));
+    }
+
+    // scr - scroll the display right 4 pixels
+    // 00fb
+    fn op_00fb(&mut self) {
+        self.scroll(4, 0);
+    }
+
+    // scl - scroll the display left 4 pixels
+    // 00fc
+    fn op_00fc(&mut self) {
+        self.scroll(-4, 0);
+    }
+
+    // exit - stop the interpreter
+    // pc is left on this instruction so stepping again exits again
+    // 00fd
+    fn op_00fd(&mut self) -> StepOutcome {
+        self.pc = self.pc.wrapping_sub(2);
+        StepOutcome::Exit
+    }
+
+    // low - switch to the 64x32 display
+    // xo-chip also clears the display
+    // 00fe
+    fn op_00fe(&mut self) {
+        self.hires = false;
+        if self.platform.supports_xochip() {
+            self.vram = [[0x0; HIRES_HEIGHT]; HIRES_WIDTH];
+        }
+    }
+
+    // high - switch to the 128x64 display
+    // xo-chip also clears the display
+    // 00ff
+    fn op_00ff(&mut self) {
+        self.hires = true;
+        if self.platform.supports_xochip() {
+            self.vram = [[0x0; HIRES_HEIGHT]; HIRES_WIDTH];
+        }
     }
 
     // ret - return from subroutine
     // 00ee
-    fn op_00ee(&mut self, _inst: u16) {
+    fn op_00ee(&mut self) -> Result<(), CpuError> {
+        if self.sp == 0 {
+            return Err(CpuError::StackUnderflow {
+                pc: self.pc.wrapping_sub(2),
+            });
+        }
         self.pc = self.stack[self.sp as usize];
         self.sp -= 1;
-        // self.pc = self.stack[self.sp as usize];
+        Ok(())
     }
 
     // jp - jump to location
     // 1nnn
-    fn op_1nnn(&mut self, inst: u16) {
-        self.pc = inst & 0x0fff;
+    fn op_1nnn(&mut self, nnn: u16) {
+        self.pc = nnn;
     }
 
     // call - call subroutine
     // 2nnn
-    fn op_2nnn(&mut self, inst: u16) {
-        // need to subtract 2 to get the current instruction being run
+    fn op_2nnn(&mut self, nnn: u16) -> Result<(), CpuError> {
+        // slot 0 is never used, so the deepest call uses the last slot
+        if self.sp as usize + 1 >= self.stack.len() {
+            // need to subtract 2 to get the current instruction being run
+            return Err(CpuError::StackOverflow {
+                pc: self.pc.wrapping_sub(2),
+            });
+        }
         self.sp += 1;
         self.stack[self.sp as usize] = self.pc;
-        self.pc = inst & 0x0fff;
+        self.pc = nnn;
+        Ok(())
     }
 
     // se - skip next instruction if Vx = kk
     // 3xkk
-    fn op_3xkk(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let val = (inst & 0x00ff) as u8;
+    fn op_3xkk(&mut self, vx: usize, val: u8) {
         if self.reg[vx] == val {
-            self.pc += 2;
+            self.skip();
         }
     }
 
     // sne - skip next instruction id vx != kk
     // 4xkk
-    fn op_4xkk(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let val = (inst & 0x00ff) as u8;
+    fn op_4xkk(&mut self, vx: usize, val: u8) {
         if self.reg[vx] != val {
-            self.pc += 2;
+            self.skip();
         }
     }
 
     // reg se - skips the next if instruction is vx == vy
     // 5xy0
-    fn op_5xy0(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
+    fn op_5xy0(&mut self, vx: usize, vy: usize) {
         if self.reg[vx] == self.reg[vy] {
-            self.pc += 2;
+            self.skip();
+        }
+    }
+
+    // ld [i], vx - vy
+    // store vx through vy in memory starting at i, in either order, i is left alone
+    // 5xy2
+    fn op_5xy2(&mut self, vx: usize, vy: usize) -> Result<(), CpuError> {
+        for (offset, reg) in Self::register_range(vx, vy).enumerate() {
+            self.write_ram(self.i as usize + offset, self.reg[reg])?;
+        }
+        Ok(())
+    }
+
+    // ld vx - vy, [i]
+    // load vx through vy from memory starting at i, in either order, i is left alone
+    // 5xy3
+    fn op_5xy3(&mut self, vx: usize, vy: usize) -> Result<(), CpuError> {
+        for (offset, reg) in Self::register_range(vx, vy).enumerate() {
+            self.reg[reg] = self.read_ram(self.i as usize + offset)?;
+        }
+        Ok(())
+    }
+
+    fn register_range(vx: usize, vy: usize) -> Box<dyn Iterator<Item = usize>> {
+        if vx <= vy {
+            Box::new(vx..=vy)
+        } else {
+            Box::new((vy..=vx).rev())
         }
     }
 
     // ld - put value kk into register vx
     // 6xkk
-    fn op_6xkk(&mut self, inst: u16) {
-        let register = ((inst & 0x0f00) >> 8) as usize;
-        let value = (inst & 0x00ff) as u8;
+    fn op_6xkk(&mut self, register: usize, value: u8) {
         self.reg[register] = value;
     }
 
     // add - add vx by kk
     // 7xkk
-    fn op_7xkk(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let value = (inst & 0x00ff) as u16;
+    fn op_7xkk(&mut self, vx: usize, value: u8) {
+        let value = value as u16;
         let x = self.reg[vx] as u16;
         let result = value + x;
         self.reg[vx] = result as u8;
@@ -187,201 +731,240 @@ impl Cpu {
 
     // ld - store value in register y into register x
     // 8xy0
-    pub fn op_8xy0(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
+    pub fn op_8xy0(&mut self, vx: usize, vy: usize) {
         self.reg[vx] = self.reg[vy];
     }
 
     // or - bit wise or on registers vx and vy where the result goes into vx
     // 8xy1
-    pub fn op_8xy1(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
+    pub fn op_8xy1(&mut self, vx: usize, vy: usize) {
         self.reg[vx] |= self.reg[vy];
+        if self.quirks.vf_reset {
+            self.reg[0xf] = 0;
+        }
     }
 
     // and - bit wise an on rgisters vx and vy with the result going into vx
     // 8xy2
-    pub fn op_8xy2(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
+    pub fn op_8xy2(&mut self, vx: usize, vy: usize) {
         self.reg[vx] &= self.reg[vy];
+        if self.quirks.vf_reset {
+            self.reg[0xf] = 0;
+        }
     }
 
     // xor - bitwise exclusive or on registers vx and vy with the results going into vx
     // 8xy3
-    pub fn op_8xy3(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
+    pub fn op_8xy3(&mut self, vx: usize, vy: usize) {
         self.reg[vx] ^= self.reg[vy];
+        if self.quirks.vf_reset {
+            self.reg[0xf] = 0;
+        }
     }
 
     // add reg - add register contents of vx and vy
     // placing result into vx
-    // setting carry flag
+    // setting carry flag, written last so it wins when x is f
     // 8xy4
-    pub fn op_8xy4(&mut self, inst: u16) {
-        self.reg[0xf] = 0;
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
-        let sum: u16 = self.reg[vx] as u16 + self.reg[vy] as u16;
-        if sum > 255 {
-            self.reg[0xf] = 1;
-        }
-        self.reg[vx] = (sum & 0x00ff) as u8;
+    pub fn op_8xy4(&mut self, vx: usize, vy: usize) {
+        let (sum, carry) = self.reg[vx].overflowing_add(self.reg[vy]);
+        self.reg[vx] = sum;
+        self.reg[0xf] = carry as u8;
     }
 
     // sub reg - subtract registers contents vy from vx
     // result in vx
-    // setting NOT borrow flag
+    // setting NOT borrow flag, written last so it wins when x is f
     // 8xy5
-    pub fn op_8xy5(&mut self, inst: u16) {
-        self.reg[0xf] = 0;
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
-        if self.reg[vx] > self.reg[vy] {
-            self.reg[0xf] = 1;
-        }
-
-        let x: i16 = self.reg[vx] as i16;
-        let y: i16 = self.reg[vy] as i16;
-        let z: i16 = x - y;
-        // self.reg[vx as usize] -= self.reg[vy as usize];
-        self.reg[vx as usize] = (z & 0x00ff) as u8;
+    pub fn op_8xy5(&mut self, vx: usize, vy: usize) {
+        let (x, y) = (self.reg[vx], self.reg[vy]);
+        self.reg[vx] = x.wrapping_sub(y);
+        self.reg[0xf] = (x >= y) as u8;
     }
 
     // shr - shift right by 1
+    // shifts vy into vx with the shift quirk
     // 8xy6
-    pub fn op_8xy6(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        self.reg[0xf] = self.reg[vx] & 0x1;
-
-        self.reg[vx as usize] >>= 1;
+    pub fn op_8xy6(&mut self, vx: usize, vy: usize) {
+        let val = if self.quirks.shift_uses_vy {
+            self.reg[vy]
+        } else {
+            self.reg[vx]
+        };
+        self.reg[vx] = val >> 1;
+        self.reg[0xf] = val & 0x1;
     }
 
     // subn
     // vx = vy - vx
+    // setting NOT borrow flag, written last so it wins when x is f
     // 8xy7
-    pub fn op_8xy7(&mut self, inst: u16) {
-        self.reg[0xf] = 0;
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
-        if self.reg[vy] > self.reg[vx] {
-            self.reg[0xf] = 1;
-        }
-        let x = self.reg[vx] as i16;
-        let y = self.reg[vy] as i16;
-        let z: i16 = y - x;
-        // self.reg[vx as usize] = self.reg[vy as usize] - self.reg[vx as usize];
-        self.reg[vx] = (z & 0x00ff) as u8;
+    pub fn op_8xy7(&mut self, vx: usize, vy: usize) {
+        let (x, y) = (self.reg[vx], self.reg[vy]);
+        self.reg[vx] = y.wrapping_sub(x);
+        self.reg[0xf] = (y >= x) as u8;
     }
 
     // shl
+    // shifts vy into vx with the shift quirk
     // 8xye
-    pub fn op_8xye(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        self.reg[0xf] = (self.reg[vx] & 0x80) >> 7;
-        self.reg[vx] <<= 1;
+    pub fn op_8xye(&mut self, vx: usize, vy: usize) {
+        let val = if self.quirks.shift_uses_vy {
+            self.reg[vy]
+        } else {
+            self.reg[vx]
+        };
+        self.reg[vx] = val << 1;
+        self.reg[0xf] = (val & 0x80) >> 7;
     }
 
     // sne - skip next inst if vx != vy
     // 9xy0
-    pub fn op_9xy0(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
+    pub fn op_9xy0(&mut self, vx: usize, vy: usize) {
         if self.reg[vx] != self.reg[vy] {
-            self.pc += 2;
+            self.skip();
         }
     }
 
     // ls immed - the value of register i is set to nnn
     // annn
-    pub fn op_annn(&mut self, inst: u16) {
-        let val = inst & 0x0fff;
+    pub fn op_annn(&mut self, val: u16) {
         self.i = val;
     }
 
     // jump reg - jump to location nnn + v0
+    // nnn + vx with the jump quirk
     // bnnn
-    pub fn op_bnnn(&mut self, inst: u16) {
-        let val = (inst & 0x0fff) as u16;
-        self.pc = self.reg[0] as u16 + val;
+    pub fn op_bnnn(&mut self, val: u16) {
+        let vx = if self.quirks.jump_uses_vx {
+            (val >> 8) as usize
+        } else {
+            0
+        };
+        self.pc = self.reg[vx] as u16 + val;
     }
 
     // rnd - set vx = random byte and kk
     // interpretor generate random number from 0 to 255, which is then ANDed with kk
     // cxkk
-    pub fn op_cxkk(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let rand: u16 = self.rand.next_u16();
-        let val: u16 = inst & 0x00ff;
-        let result: u16 = rand & val;
-        self.reg[vx] = (result & 0x00ff) as u8;
+    pub fn op_cxkk(&mut self, vx: usize, val: u8) {
+        let rand = self.rand.next_u8(&self.ram);
+        self.reg[vx] = rand & val;
     }
 
     // drw
     // display n-byte sprite start at memory location I at (Vx, Vy), set VF = collision
+    // on super-chip n = 0 draws a 16x16 sprite made of 32 bytes
+    // on xo-chip each selected plane draws its own sprite, one after the other in memory
     // dxyn
-    pub fn op_dxyn(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let vy = ((inst & 0x00f0) >> 4) as usize;
-        let height = (inst & 0x000f) as usize;
-
-        let mut y_pos = (self.reg[vy] % 32) as usize;
-
+    pub fn op_dxyn(&mut self, vx: usize, vy: usize, height: u8) -> Result<(), CpuError> {
+        let height = height as usize;
+        let (rows, columns) = if height == 0 && self.platform.supports_superchip() {
+            (16, 16)
+        } else {
+            (height, 8)
+        };
+        let bytes_per_row = columns / 8;
+        let width = self.display_width();
+        let display_height = self.display_height();
+        // the start position always wraps, only the rest of the sprite is clipped
+        let clip = self.quirks.clip_sprites;
+
+        let mut sprite = self.i as usize;
         self.reg[0xf] = 0;
-        for idx in 0..height {
-            let mut x_pos = (self.reg[vx] % 64) as usize;
-            let byte: u8 = self.ram[self.i as usize + idx];
-            let mut mask: u8 = 0x80;
-            let mut shift = 7;
-            for _ in 0..8 {
-                let pixel = (byte & mask) >> shift;
-                mask >>= 1;
-                shift -= 1;
-                if self.vram[x_pos][y_pos] > 0 && pixel > 0 {
-                    self.reg[0xf] = 1;
+        for plane in [0x1, 0x2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            let y_start = self.reg[vy] as usize % display_height;
+            let x_start = self.reg[vx] as usize % width;
+            for row in 0..rows {
+                let y_pos = y_start + row;
+                if clip && y_pos >= display_height {
+                    break;
+                }
+                let y_pos = y_pos % display_height;
+                let mut byte = 0x0;
+                for column in 0..columns {
+                    let x_pos = x_start + column;
+                    if clip && x_pos >= width {
+                        break;
+                    }
+                    let x_pos = x_pos % width;
+                    // each sprite byte is read once
+                    if column % 8 == 0 {
+                        byte = self.read_ram(sprite + row * bytes_per_row + column / 8)?;
+                    }
+                    if (byte >> (7 - column % 8)) & 0x1 > 0 {
+                        if self.vram[x_pos][y_pos] & plane > 0 {
+                            self.reg[0xf] = 1;
+                        }
+                        self.vram[x_pos][y_pos] ^= plane;
+                    }
                 }
-                self.vram[x_pos][y_pos] ^= pixel;
-                x_pos = (x_pos + 1) % 64;
             }
-            y_pos = (y_pos + 1) % 32;
+            sprite += rows * bytes_per_row;
         }
+        Ok(())
     }
 
     // skp - skip next instruction if key with the calue of Vx is not pressed
     // ex9e
-    pub fn op_ex9e(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let key = self.reg[vx] as usize;
+    pub fn op_ex9e(&mut self, vx: usize) {
+        let key = (self.reg[vx] & 0x0f) as usize;
         if self.keypad[key] > 0 {
-            // research this
-            self.pc += 2;
+            self.skip();
         }
     }
 
     // skpn
     // exa1
-    pub fn op_exa1(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let key = self.reg[vx] as usize;
+    pub fn op_exa1(&mut self, vx: usize) {
+        let key = (self.reg[vx] & 0x0f) as usize;
         if self.keypad[key] < 1 {
-            self.pc += 2;
+            self.skip();
+        }
+    }
+
+    // ld i, nnnn
+    // set i to the 16 bit address in the next two bytes
+    // f000 nnnn
+    pub fn op_f000(&mut self) -> Result<(), CpuError> {
+        let msb = self.read_ram(self.pc as usize)? as u16;
+        let lsb = self.read_ram(self.pc as usize + 1)? as u16;
+        self.i = (msb << 8) | lsb;
+        self.pc = self.pc.wrapping_add(2);
+        Ok(())
+    }
+
+    // plane n
+    // select the planes drawn to, cleared and scrolled, n is a bit mask
+    // fn01
+    pub fn op_fn01(&mut self, planes: u8) {
+        self.planes = planes & 0x3;
+    }
+
+    // audio
+    // load the 16 byte audio pattern at i
+    // f002
+    pub fn op_f002(&mut self) -> Result<(), CpuError> {
+        let mut pattern = [0x0; 16];
+        for (idx, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.read_ram(self.i as usize + idx)?;
         }
+        self.pattern = Some(pattern);
+        Ok(())
     }
 
     // ld dt - set Vx = delay timer value
     // fx07
-    pub fn op_fx07(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
+    pub fn op_fx07(&mut self, vx: usize) {
         self.reg[vx] = self.dt;
     }
 
     // ld keypress - wait for key press, stoe the value of the key in vx
     // fx0a
-    pub fn op_fx0a(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
+    pub fn op_fx0a(&mut self, vx: usize) -> StepOutcome {
         if self.keypad[0] > 0 {
             self.reg[vx] = 0;
         } else if self.keypad[1] > 0 {
@@ -415,73 +998,104 @@ impl Cpu {
         } else if self.keypad[15] > 0 {
             self.reg[vx] = 15;
         } else {
-            self.pc -= 2;
+            self.pc = self.pc.wrapping_sub(2);
+            return StepOutcome::WaitingForKey;
         }
+        StepOutcome::Executed
     }
 
     // ld set delay - delay time = vx
     // fx15
-    pub fn op_fx15(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
+    pub fn op_fx15(&mut self, vx: usize) {
         self.dt = self.reg[vx];
     }
 
     // ld st, set sound timer = vx
     // fx18
-    pub fn op_fx18(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
+    pub fn op_fx18(&mut self, vx: usize) {
         self.st = self.reg[vx];
     }
 
     // add i, vx
     // i = i + vx
     // fx1e
-    pub fn op_fx1e(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        self.i += self.reg[vx] as u16;
+    pub fn op_fx1e(&mut self, vx: usize) {
+        self.i = self.i.wrapping_add(self.reg[vx] as u16);
     }
 
     // ld f, vx
     // set i = location of sprite for digit vx
     // fx29
-    pub fn op_fx29(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        let digit = self.reg[vx] as u16;
-        // 100 is supposed to be fontsize address
-        self.i = 100 + (5 * digit);
+    pub fn op_fx29(&mut self, vx: usize) {
+        let digit = (self.reg[vx] & 0x0f) as u16;
+        self.i = self.font_address + (font::FONT_GLYPH_SIZE * digit);
+    }
+
+    // ld hf, vx
+    // set i = location of the large sprite for digit vx
+    // fx30
+    pub fn op_fx30(&mut self, vx: usize) {
+        let digit = (self.reg[vx] & 0x0f) as u16;
+        self.i = self.big_font_address + (font::BIG_FONT_GLYPH_SIZE * digit);
+    }
+
+    // pitch vx
+    // set the audio pattern playback rate
+    // fx3a
+    pub fn op_fx3a(&mut self, vx: usize) {
+        self.pitch = self.reg[vx];
     }
 
     // ld b, vx
     // store BCD representation of Vx in memory locations I, I + 1, I + 2
     // fx33
-    pub fn op_fx33(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
+    pub fn op_fx33(&mut self, vx: usize) -> Result<(), CpuError> {
         let mut val = self.reg[vx];
-        self.ram[self.i as usize + 2] = val % 10;
+        self.write_ram(self.i as usize + 2, val % 10)?;
         val /= 10;
-        self.ram[self.i as usize + 1] = val % 10;
+        self.write_ram(self.i as usize + 1, val % 10)?;
         val /= 10;
-        self.ram[self.i as usize] = val % 10;
+        self.write_ram(self.i as usize, val % 10)
     }
 
     // ld [i], vx
     // store contents of registers v0 trhough vx to memory starting at index location
     // fx55
-    pub fn op_fx55(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        for idx in 0..vx {
-            self.ram[self.i as usize + idx] = self.reg[idx as usize];
+    pub fn op_fx55(&mut self, vx: usize) -> Result<(), CpuError> {
+        for idx in 0..=vx {
+            self.write_ram(self.i as usize + idx, self.reg[idx])?;
         }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(vx as u16 + 1);
+        }
+        Ok(())
     }
 
     // ld vx, [i]
     // load contents into registers from ram
     // fx65
-    pub fn op_fx65(&mut self, inst: u16) {
-        let vx = ((inst & 0x0f00) >> 8) as usize;
-        for idx in 0..vx {
-            self.reg[idx as usize] = self.ram[self.i as usize + idx];
+    pub fn op_fx65(&mut self, vx: usize) -> Result<(), CpuError> {
+        for idx in 0..=vx {
+            self.reg[idx] = self.read_ram(self.i as usize + idx)?;
+        }
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(vx as u16 + 1);
         }
+        Ok(())
+    }
+
+    // ld r, vx
+    // store v0 through vx in the rpl user flags, x < 8
+    // fx75
+    pub fn op_fx75(&mut self, vx: usize) {
+        self.rpl[..=vx].copy_from_slice(&self.reg[..=vx]);
+    }
+
+    // ld vx, r
+    // load v0 through vx from the rpl user flags, x < 8
+    // fx85
+    pub fn op_fx85(&mut self, vx: usize) {
+        self.reg[..=vx].copy_from_slice(&self.rpl[..=vx]);
     }
 }
 
@@ -492,13 +1106,13 @@ mod tests {
     #[test]
     fn test_op_00e0() {
         let mut cpu = Cpu::new();
-        cpu.vram = [[0x1; 32]; 64];
+        cpu.vram = [[0x1; HIRES_HEIGHT]; HIRES_WIDTH];
         for i in 0..cpu.vram.len() {
             for j in 0..cpu.vram[0].len() {
                 assert_eq!(cpu.vram[i][j], 0x1);
             }
         }
-        cpu.op_00e0(0x00e0);
+        cpu.op_00e0();
         for i in 0..cpu.vram.len() {
             for j in 0..cpu.vram[0].len() {
                 assert_eq!(cpu.vram[i][j], 0x0);
@@ -510,9 +1124,9 @@ mod tests {
     fn test_op_1nnn() {
         let mut cpu = Cpu::new();
         let rom: Vec<u8> = [0x12, 0x00, 0x00, 0xe0].to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x200);
     }
 
@@ -525,12 +1139,12 @@ mod tests {
             0x00, 0xee, // instruction to return
         ]
         .to_vec();
-        cpu.load_rom(rom);
-        cpu.step();
+        cpu.load_rom(rom).unwrap();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.sp, 1);
         assert_eq!(cpu.stack[cpu.sp as usize], 0x202);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
     }
 
@@ -546,10 +1160,10 @@ mod tests {
             0x00, 0x00,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         cpu.reg[1] = 0x11;
         assert_eq!(cpu.pc, 0x200);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
     }
 
@@ -566,10 +1180,10 @@ mod tests {
             // instruction ran if equal
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         cpu.reg[1] = 0x12;
         assert_eq!(cpu.pc, 0x200);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
     }
 
@@ -586,11 +1200,11 @@ mod tests {
             0x12, 00,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         cpu.reg[1] = 1;
         cpu.reg[2] = 1;
         assert_eq!(cpu.pc, 0x200);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
     }
 
@@ -605,10 +1219,10 @@ mod tests {
             0x12, 0x00,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0xff);
     }
@@ -624,13 +1238,13 @@ mod tests {
             0x71, 0x01,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x02);
     }
@@ -646,13 +1260,13 @@ mod tests {
             0x71, 0xff,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x02);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x01);
     }
@@ -668,15 +1282,15 @@ mod tests {
             0x81, 0x20,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[2], 0x00);
         assert_eq!(cpu.reg[1], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[2], 0xff);
         assert_eq!(cpu.reg[1], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0xff);
         assert_eq!(cpu.reg[1], 0xff);
@@ -695,19 +1309,19 @@ mod tests {
             0x81, 0x21,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[2], 0x02);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x206);
         assert_eq!(cpu.reg[1], (0x01 | 0x02));
         assert_eq!(cpu.reg[2], 0x02);
@@ -726,19 +1340,19 @@ mod tests {
             0x81, 0x22,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[2], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x206);
         assert_eq!(cpu.reg[1], (0x01 & 0x01));
         assert_eq!(cpu.reg[2], 0x01);
@@ -757,19 +1371,19 @@ mod tests {
             0x81, 0x23,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[2], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x206);
         assert_eq!(cpu.reg[1], (0x01 ^ 0x01));
         assert_eq!(cpu.reg[2], 0x01);
@@ -789,17 +1403,17 @@ mod tests {
             0x81, 0x24,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0xff);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[0xf], 0x01);
     }
@@ -818,17 +1432,17 @@ mod tests {
             0x81, 0x24,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0x08);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.reg[1], 0x09);
         assert_eq!(cpu.reg[0xf], 0x00);
     }
@@ -846,22 +1460,58 @@ mod tests {
             0x81, 0x25,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x02);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0x01);
         assert_eq!(cpu.reg[0xf], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[0xf], 0x01);
     }
 
+    #[test]
+    fn test_op_8xy_flag_into_vf() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // load 0xfe into register f
+            0x6f, 0xfe, // address 0x202
+            // load 0x03 into register 1
+            0x61, 0x03, // address 0x204
+            // add register 1 to register f, the carry replaces the sum
+            0x8f, 0x14, // address 0x206
+            // subtract register 1 from register f, 1 - 3 borrows
+            0x8f, 0x15, // address 0x208
+            // register f = register 1 - register f, 3 - 0 does not borrow
+            0x8f, 0x17, // address 0x20a
+            // load 0x03 into register 2
+            0x62, 0x03, // address 0x20c
+            // subtract equal registers, no borrow
+            0x81, 0x25,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[0xf], 0x01);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[0xf], 0x00);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[0xf], 0x01);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[1], 0x00);
+        assert_eq!(cpu.reg[0xf], 0x01);
+    }
+
     #[test]
     fn test_op_8xy5_no_carry_flag() {
         let mut cpu = Cpu::new();
@@ -875,18 +1525,18 @@ mod tests {
             0x81, 0x25,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x02);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0x03);
         assert_eq!(cpu.reg[0xf], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.reg[1], 0xff);
         assert_eq!(cpu.reg[0xf], 0x00);
     }
@@ -902,12 +1552,12 @@ mod tests {
             0x81, 0x06,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x04);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x02);
         assert_eq!(cpu.reg[0xf], 0x00);
@@ -924,12 +1574,12 @@ mod tests {
             0x81, 0x06,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x03);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x01);
         assert_eq!(cpu.reg[0xf], 0x01);
@@ -948,18 +1598,18 @@ mod tests {
             0x81, 0x27,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
         assert_eq!(cpu.reg[0xf], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0x05);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x206);
         assert_eq!(cpu.reg[1], 0x04);
         assert_eq!(cpu.reg[0xf], 0x01);
@@ -978,18 +1628,18 @@ mod tests {
             0x81, 0x27,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
         assert_eq!(cpu.reg[0xf], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x06);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0x05);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x206);
         assert_eq!(cpu.reg[1], 0xff);
         assert_eq!(cpu.reg[0xf], 0x00);
@@ -1006,14 +1656,14 @@ mod tests {
             0x81, 0x0e,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[0xf], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x04);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x08);
         assert_eq!(cpu.reg[0xf], 0x00);
@@ -1030,14 +1680,14 @@ mod tests {
             0x81, 0x0e,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[0xf], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x81);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[1], 0x02);
         assert_eq!(cpu.reg[0xf], 0x01);
@@ -1061,20 +1711,20 @@ mod tests {
             0x61, 0x02,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0x02);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x208);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x20a);
         assert_eq!(cpu.reg[1], 0x02);
     }
@@ -1097,20 +1747,20 @@ mod tests {
             0x61, 0x02,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
         assert_eq!(cpu.reg[2], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x204);
         assert_eq!(cpu.reg[2], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x206);
         assert_eq!(cpu.reg[1], 0x01);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x208);
         assert_eq!(cpu.reg[1], 0x04);
     }
@@ -1124,9 +1774,9 @@ mod tests {
             0xa1, 0x11,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.i, 0x0000);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.i, 0x0111);
     }
 
@@ -1147,20 +1797,100 @@ mod tests {
             0x61, 0x04,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[0], 0x00);
         assert_eq!(cpu.reg[1], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[0], 0x02);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x206);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x208);
         assert_eq!(cpu.reg[1], 0x04);
     }
 
+    #[test]
+    fn test_set_key() {
+        let mut cpu = Cpu::new();
+        cpu.set_key(0xa, true);
+        assert_eq!(cpu.keypad[0xa], 1);
+        cpu.set_key(0xa, false);
+        assert_eq!(cpu.keypad[0xa], 0);
+    }
+
+    #[test]
+    fn test_keypad_mask() {
+        let mut cpu = Cpu::new();
+        cpu.set_key(0x0, true);
+        cpu.set_key(0xf, true);
+        assert_eq!(cpu.keypad_mask(), 0x8001);
+        cpu.set_keypad_mask(0x0010);
+        assert!(cpu.key(0x4));
+        assert!(!cpu.key(0x0));
+        assert_eq!(cpu.keypad_mask(), 0x0010);
+    }
+
+    #[test]
+    fn test_op_ex9e() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // skip if the key in v1 is pressed
+            0xe1, 0x9e, // address 0x202
+            // skip if the key in v1 is pressed
+            0xe1, 0x9e,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.reg[1] = 0x5;
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        cpu.set_key(0x5, true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn test_op_exa1() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // skip if the key in v1 is not pressed
+            0xe1, 0xa1, // address 0x202
+            // skip if the key in v1 is not pressed
+            0xe1, 0xa1,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.reg[1] = 0x5;
+        cpu.set_key(0x5, true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        cpu.set_key(0x5, false);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x206);
+    }
+
+    #[test]
+    fn test_op_fx0a() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // wait for a key press, store it in v1
+            0xf1, 0x0a,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x200);
+        cpu.set_key(0xc, true);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.reg[1], 0xc);
+    }
+
     #[test]
     fn test_op_fx07() {
         let mut cpu = Cpu::new();
@@ -1170,11 +1900,11 @@ mod tests {
             0xf1, 0x07,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         cpu.dt = 0x01;
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.reg[1], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.reg[1], 0x01);
     }
@@ -1188,11 +1918,11 @@ mod tests {
             0xf1, 0x15,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         cpu.reg[1] = 0x01;
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.dt, 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.dt, 0x01);
     }
@@ -1206,15 +1936,53 @@ mod tests {
             0xf1, 0x18,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         cpu.reg[1] = 0x01;
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.st, 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.st, 0x01);
     }
 
+    #[test]
+    fn test_tick_timers() {
+        let mut cpu = Cpu::new();
+        cpu.dt = 0x02;
+        cpu.st = 0x01;
+        cpu.tick_timers();
+        assert_eq!(cpu.dt, 0x01);
+        assert_eq!(cpu.st, 0x00);
+        cpu.tick_timers();
+        assert_eq!(cpu.dt, 0x00);
+        assert_eq!(cpu.st, 0x00);
+        cpu.tick_timers();
+        assert_eq!(cpu.dt, 0x00);
+        assert_eq!(cpu.st, 0x00);
+    }
+
+    #[test]
+    fn test_timers_independent_of_step() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // load 0x03 into register 1
+            0x61, 0x03, // address 0x202
+            // set delay timer to v1
+            0xf1, 0x15, // address 0x204
+            // jump to self
+            0x12, 0x04,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        for _ in 0..100 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.dt, 0x03);
+        cpu.tick_timers();
+        assert_eq!(cpu.dt, 0x02);
+    }
+
     #[test]
     fn test_op_fx1e() {
         let mut cpu = Cpu::new();
@@ -1224,24 +1992,76 @@ mod tests {
             0xf1, 0x1e,
         ]
         .to_vec();
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         cpu.i = 0x01;
         cpu.reg[1] = 0x01;
         assert_eq!(cpu.pc, 0x200);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         assert_eq!(cpu.i, 0x02);
     }
 
-    // ld f, vx
-    // set i = location of sprite for digit vx
-    // fx29
-    // pub fn op_fx29(&mut self, inst: u16) {
-    //     let vx = ((inst & 0x0f00) >> 8) as usize;
-    //     let digit = self.reg[vx] as u16;
-    //     // 100 is supposed to be fontsize address
-    //     self.i = 100 + (5 * digit);
-    // }
+    #[test]
+    fn test_op_fx29() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // point index register at the sprite for the digit in v1
+            0xf1, 0x29,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.reg[1] = 0x0a;
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.i, font::FONT_ADDRESS + 0x0a * 5);
+        let glyph = &cpu.ram[cpu.i as usize..cpu.i as usize + 5];
+        assert_eq!(glyph, &font::FONT[50..55]);
+    }
+
+    #[test]
+    fn test_fonts_installed() {
+        let cpu = Cpu::new();
+        let small = font::FONT_ADDRESS as usize;
+        let big = font::BIG_FONT_ADDRESS as usize;
+        assert_eq!(&cpu.ram[small..small + 80], &font::FONT[..]);
+        assert_eq!(&cpu.ram[big..big + 160], &font::BIG_FONT[..]);
+    }
+
+    #[test]
+    fn test_set_font_address() {
+        let mut cpu = Cpu::new();
+        cpu.set_font_address(0x000).unwrap();
+        assert_eq!(cpu.font_address(), 0x000);
+        assert_eq!(&cpu.ram[0x000..0x050], &font::FONT[..]);
+        // the old location is cleared
+        assert!(cpu.ram[0x050..0x0a0].iter().all(|byte| *byte == 0x0));
+        cpu.reg[2] = 0x3;
+        cpu.op_fx29(2);
+        assert_eq!(cpu.i, 0x00f);
+    }
+
+    #[test]
+    fn test_set_font_address_outside_interpreter_area() {
+        let mut cpu = Cpu::new();
+        assert!(cpu.set_font_address(0x1c0).is_err());
+        assert!(cpu.set_big_font_address(0x180).is_err());
+        assert_eq!(cpu.font_address(), font::FONT_ADDRESS);
+        assert_eq!(cpu.big_font_address(), font::BIG_FONT_ADDRESS);
+    }
+
+    #[test]
+    fn test_custom_font_survives_reset() {
+        let mut cpu = Cpu::new();
+        cpu.load_font([0xaa; 80]);
+        cpu.load_rom([0x12, 0x00].to_vec()).unwrap();
+        cpu.reg[1] = 0x1;
+        cpu.reset();
+        assert_eq!(cpu.reg[1], 0x0);
+        assert_eq!(cpu.ram[0x200], 0x0);
+        let small = font::FONT_ADDRESS as usize;
+        assert!(cpu.ram[small..small + 80].iter().all(|byte| *byte == 0xaa));
+    }
 
     // ld b, vx
     // store BCD representation of Vx in memory locations I, I + 1, I + 2
@@ -1256,6 +2076,31 @@ mod tests {
     //     self.ram[self.i as usize] = val % 10;
     // }
 
+    #[test]
+    fn test_execute() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0x202;
+        cpu.execute(Instruction::LoadImm { x: 3, kk: 0x42 })
+            .unwrap();
+        assert_eq!(cpu.reg[3], 0x42);
+        cpu.execute(Instruction::Call(0x300)).unwrap();
+        assert_eq!(cpu.pc, 0x300);
+        cpu.ram[0x300] = 0x81;
+        cpu.ram[0x301] = 0x91;
+        assert_eq!(
+            cpu.instruction_at(0x300),
+            Some(Instruction::Or { x: 1, y: 9 })
+        );
+        // super-chip only, pc - 2 is reported
+        assert_eq!(
+            cpu.execute(Instruction::High),
+            Err(CpuError::InvalidOpcode {
+                pc: 0x2fe,
+                opcode: 0x00ff
+            })
+        );
+    }
+
     #[test]
     fn test_op_fx55() {
         let mut cpu = Cpu::new();
@@ -1282,10 +2127,10 @@ mod tests {
         cpu.reg[0xe] = 1;
         cpu.reg[0xf] = 1;
         cpu.i = 0x000;
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.ram[cpu.i as usize], 0x00);
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         for i in 0..15 {
             assert_eq!(cpu.ram[cpu.i as usize + i], 0x01);
@@ -1317,16 +2162,591 @@ mod tests {
         cpu.ram[0xd] = 1;
         cpu.ram[0xe] = 1;
         cpu.ram[0xf] = 1;
-        cpu.load_rom(rom);
+        cpu.load_rom(rom).unwrap();
         assert_eq!(cpu.pc, 0x200);
         assert_eq!(cpu.i, 0x000);
         for i in 0..15 {
             assert_eq!(cpu.reg[i], 0x00);
         }
-        cpu.step();
+        cpu.step().unwrap();
         assert_eq!(cpu.pc, 0x202);
         for i in 0..15 {
             assert_eq!(cpu.reg[i], 0x01);
         }
     }
+
+    #[test]
+    fn test_invalid_opcode() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // filler
+            0x61, 0x01, // address 0x202
+            // not an instruction
+            0x81, 0x2f,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(cpu.step(), Ok(StepOutcome::Executed));
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::InvalidOpcode {
+                pc: 0x202,
+                opcode: 0x812f
+            })
+        );
+    }
+
+    #[test]
+    fn test_stack_overflow() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // call self
+            0x22, 0x00,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        for _ in 0..15 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.step(), Err(CpuError::StackOverflow { pc: 0x200 }));
+    }
+
+    #[test]
+    fn test_stack_underflow() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // return with nothing on the stack
+            0x00, 0xee,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(cpu.step(), Err(CpuError::StackUnderflow { pc: 0x200 }));
+    }
+
+    #[test]
+    fn test_memory_out_of_bounds() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // store v0 through v2 at i
+            0xf2, 0x55,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.i = 0xfff;
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::MemoryOutOfBounds { addr: 0x1000 })
+        );
+    }
+
+    #[test]
+    fn test_fetch_out_of_bounds() {
+        let mut cpu = Cpu::new();
+        cpu.pc = 0xfff;
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::MemoryOutOfBounds { addr: 0x1000 })
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        let rom: Vec<u8> = [
+            // address 0x200
+            // random numbers into v0 through v3
+            0xc0, 0xff, 0xc1, 0xff, 0xc2, 0xff, 0xc3, 0xff,
+        ]
+        .to_vec();
+        let run = |cpu: &mut Cpu| {
+            cpu.load_rom(rom.clone()).unwrap();
+            for _ in 0..4 {
+                cpu.step().unwrap();
+            }
+            cpu.reg
+        };
+        let mut cpu = Cpu::with_seed(7);
+        let first = run(&mut cpu);
+        assert_eq!(run(&mut Cpu::with_seed(7)), first);
+        assert_ne!(run(&mut Cpu::with_seed(8)), first);
+        // reset starts over from the same seed
+        cpu.reset();
+        assert_eq!(cpu.seed(), 7);
+        assert_eq!(run(&mut cpu), first);
+    }
+
+    #[test]
+    fn test_rom_hash() {
+        let mut cpu = Cpu::new();
+        assert_eq!(cpu.rom_hash(), None);
+        cpu.load_rom(b"abc".to_vec()).unwrap();
+        assert_eq!(
+            cpu.rom_hash(),
+            Some("a9993e364706816aba3e25717850c26c9cd0d89d")
+        );
+        cpu.reset();
+        assert_eq!(cpu.rom_hash(), None);
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let mut cpu = Cpu::new();
+        assert!(cpu.load_rom(vec![0x0; 0xe00]).is_ok());
+        assert_eq!(
+            cpu.load_rom(vec![0x0; 0xe01]),
+            Err(CpuError::RomTooLarge {
+                size: 0xe01,
+                max: 0xe00
+            })
+        );
+    }
+
+    #[test]
+    fn test_waiting_for_key() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // wait for a key press
+            0xf1, 0x0a,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(cpu.step(), Ok(StepOutcome::WaitingForKey));
+        cpu.set_key(0x1, true);
+        assert_eq!(cpu.step(), Ok(StepOutcome::Executed));
+    }
+
+    #[test]
+    fn test_superchip_opcodes_need_superchip() {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // switch to high resolution
+            0x00, 0xff,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(
+            cpu.step(),
+            Err(CpuError::InvalidOpcode {
+                pc: 0x200,
+                opcode: 0x00ff
+            })
+        );
+    }
+
+    #[test]
+    fn test_op_00ff_and_00fe() {
+        let mut cpu = Cpu::with_platform(Platform::SuperChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // switch to high resolution
+            0x00, 0xff, // address 0x202
+            // switch to low resolution
+            0x00, 0xfe,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(cpu.display_width(), 64);
+        assert_eq!(cpu.display_height(), 32);
+        cpu.step().unwrap();
+        assert!(cpu.hires());
+        assert_eq!(cpu.display_width(), 128);
+        assert_eq!(cpu.display_height(), 64);
+        cpu.step().unwrap();
+        assert!(!cpu.hires());
+    }
+
+    #[test]
+    fn test_op_00cn() {
+        let mut cpu = Cpu::with_platform(Platform::SuperChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // scroll down 3 pixels
+            0x00, 0xc3,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.vram[5][0] = 1;
+        cpu.vram[5][31] = 1;
+        cpu.step().unwrap();
+        assert_eq!(cpu.vram[5][0], 0);
+        assert_eq!(cpu.vram[5][3], 1);
+        // scrolled off the bottom of the low resolution display
+        assert_eq!(cpu.vram[5][34], 0);
+    }
+
+    #[test]
+    fn test_op_00fb_and_00fc() {
+        let mut cpu = Cpu::with_platform(Platform::SuperChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // scroll right 4 pixels
+            0x00, 0xfb, // address 0x202
+            // scroll left 4 pixels
+            0x00, 0xfc, // address 0x204
+            // scroll left 4 pixels
+            0x00, 0xfc,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.vram[2][7] = 1;
+        cpu.step().unwrap();
+        assert_eq!(cpu.vram[2][7], 0);
+        assert_eq!(cpu.vram[6][7], 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.vram[2][7], 1);
+        assert_eq!(cpu.vram[6][7], 0);
+        cpu.step().unwrap();
+        assert!(cpu.vram.iter().all(|column| column.iter().all(|p| *p == 0)));
+    }
+
+    #[test]
+    fn test_op_00fd() {
+        let mut cpu = Cpu::with_platform(Platform::SuperChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // exit
+            0x00, 0xfd,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(cpu.step(), Ok(StepOutcome::Exit));
+        assert_eq!(cpu.pc, 0x200);
+    }
+
+    #[test]
+    fn test_op_dxy0() {
+        let mut cpu = Cpu::with_platform(Platform::SuperChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // switch to high resolution
+            0x00, 0xff, // address 0x202
+            // draw a 16x16 sprite at (v1, v2)
+            0xd1, 0x20,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.i = 0x300;
+        // left column of the top row and right column of the bottom row
+        cpu.ram[0x300] = 0x80;
+        cpu.ram[0x31f] = 0x01;
+        cpu.reg[1] = 100;
+        cpu.reg[2] = 40;
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pixel(100, 40), 1);
+        assert_eq!(cpu.pixel(115, 55), 1);
+        assert_eq!(cpu.pixel(101, 40), 0);
+        assert_eq!(cpu.reg[0xf], 0);
+    }
+
+    #[test]
+    fn test_op_fx30() {
+        let mut cpu = Cpu::with_platform(Platform::SuperChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // point index register at the large sprite for the digit in v1
+            0xf1, 0x30,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.reg[1] = 0x9;
+        cpu.step().unwrap();
+        assert_eq!(cpu.i, font::BIG_FONT_ADDRESS + 90);
+        let glyph = &cpu.ram[cpu.i as usize..cpu.i as usize + 10];
+        assert_eq!(glyph, &font::BIG_FONT[90..100]);
+    }
+
+    #[test]
+    fn test_op_fx75_and_fx85() {
+        let mut cpu = Cpu::with_platform(Platform::SuperChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // save v0 through v2 to the rpl flags
+            0xf2, 0x75, // address 0x202
+            // load v0 through v1 from the rpl flags
+            0xf1, 0x85, // address 0x204
+            // rpl flags only go up to v7
+            0xf8, 0x75,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.reg[0] = 0xa;
+        cpu.reg[1] = 0xb;
+        cpu.reg[2] = 0xc;
+        cpu.step().unwrap();
+        cpu.reg = [0x0; 0x10];
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[0], 0xa);
+        assert_eq!(cpu.reg[1], 0xb);
+        assert_eq!(cpu.reg[2], 0x0);
+        assert!(cpu.step().is_err());
+    }
+
+    #[test]
+    fn test_xochip_rom_larger_than_4k() {
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        assert_eq!(cpu.ram.len(), 0x10000);
+        cpu.load_rom(vec![0x0; 0x2000]).unwrap();
+        let mut cpu = Cpu::new();
+        assert!(cpu.load_rom(vec![0x0; 0x2000]).is_err());
+    }
+
+    #[test]
+    fn test_op_f000() {
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // point index register at 0x1234
+            0xf0, 0x00, 0x12, 0x34, // address 0x204
+            // skip the next instruction when v0 is 0
+            0x30, 0x00, // address 0x206
+            // a skipped long load is 4 bytes
+            0xf0, 0x00, 0xab, 0xcd,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.i, 0x1234);
+        assert_eq!(cpu.pc, 0x204);
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x20a);
+        assert_eq!(cpu.i, 0x1234);
+    }
+
+    #[test]
+    fn test_op_5xy2_and_5xy3() {
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // save v1 through v3 at i
+            0x51, 0x32, // address 0x202
+            // load v6 down to v4 from i
+            0x56, 0x43,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.i = 0x300;
+        cpu.reg[1] = 0xa;
+        cpu.reg[2] = 0xb;
+        cpu.reg[3] = 0xc;
+        cpu.step().unwrap();
+        assert_eq!(&cpu.ram[0x300..0x303], &[0xa, 0xb, 0xc]);
+        assert_eq!(cpu.i, 0x300);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[6], 0xa);
+        assert_eq!(cpu.reg[5], 0xb);
+        assert_eq!(cpu.reg[4], 0xc);
+    }
+
+    #[test]
+    fn test_xochip_opcodes_need_xochip() {
+        for rom in [[0x51, 0x32], [0xf0, 0x00], [0xf1, 0x01], [0x00, 0xd1]] {
+            let mut cpu = Cpu::with_platform(Platform::SuperChip);
+            cpu.load_rom(rom.to_vec()).unwrap();
+            assert!(cpu.step().is_err());
+        }
+    }
+
+    #[test]
+    fn test_draw_two_planes() {
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // select both planes
+            0xf3, 0x01, // address 0x202
+            // draw a 1 row sprite at v0, v0, one byte per plane
+            0xd0, 0x01, // address 0x204
+            // select the second plane
+            0xf2, 0x01, // address 0x206
+            // clear it
+            0x00, 0xe0,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.i = 0x300;
+        cpu.ram[0x300] = 0b1100_0000;
+        cpu.ram[0x301] = 0b1000_0000;
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pixel(0, 0), 0x3);
+        assert_eq!(cpu.pixel(1, 0), 0x1);
+        assert_eq!(cpu.reg[0xf], 0);
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.pixel(0, 0), 0x1);
+        assert_eq!(cpu.pixel(1, 0), 0x1);
+    }
+
+    #[test]
+    fn test_op_00dn() {
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // scroll up 2 pixels
+            0x00, 0xd2,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.vram[5][2] = 0x1;
+        cpu.vram[5][0] = 0x1;
+        cpu.step().unwrap();
+        assert_eq!(cpu.pixel(5, 0), 0x1);
+        assert_eq!(cpu.pixel(5, 2), 0x0);
+    }
+
+    #[test]
+    fn test_op_f002_and_fx3a() {
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // load the audio pattern at i
+            0xf0, 0x02, // address 0x202
+            // set the pitch to v1
+            0xf1, 0x3a,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(cpu.audio_pattern(), None);
+        cpu.i = 0x300;
+        cpu.ram[0x300] = 0xff;
+        cpu.reg[1] = 112;
+        cpu.step().unwrap();
+        let pattern = cpu.audio_pattern().unwrap();
+        assert_eq!(pattern.bits[0], 0xff);
+        assert_eq!(pattern.rate(), 4000.0);
+        cpu.step().unwrap();
+        assert!((cpu.audio_pattern().unwrap().rate() - 8000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_shift_quirk() {
+        let mut cpu = Cpu::with_platform(Platform::CosmacVip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // shift v2 right into v1
+            0x81, 0x26, // address 0x202
+            // shift v2 left into v1
+            0x81, 0x2e,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.reg[1] = 0xff;
+        cpu.reg[2] = 0x81;
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[1], 0x40);
+        assert_eq!(cpu.reg[0xf], 1);
+        cpu.step().unwrap();
+        assert_eq!(cpu.reg[1], 0x02);
+        assert_eq!(cpu.reg[0xf], 1);
+    }
+
+    #[test]
+    fn test_load_store_quirk() {
+        let mut cpu = Cpu::with_platform(Platform::CosmacVip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // store v0 through v2
+            0xf2, 0x55, // address 0x202
+            // load v0 through v3
+            0xf3, 0x65,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.i = 0x300;
+        cpu.step().unwrap();
+        assert_eq!(cpu.i, 0x303);
+        cpu.step().unwrap();
+        assert_eq!(cpu.i, 0x307);
+    }
+
+    #[test]
+    fn test_jump_quirk() {
+        let mut cpu = Cpu::with_platform(Platform::Chip48);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // jump to 0x300 + v3
+            0xb3, 0x00,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.reg[0] = 0x10;
+        cpu.reg[3] = 0x20;
+        cpu.step().unwrap();
+        assert_eq!(cpu.pc, 0x320);
+    }
+
+    #[test]
+    fn test_vf_reset_quirk() {
+        for (inst, quirk) in [(0x11, false), (0x11, true), (0x12, true), (0x13, true)] {
+            let mut cpu = Cpu::new();
+            cpu.set_quirks(Quirks {
+                vf_reset: quirk,
+                ..Quirks::default()
+            });
+            // logic op on v0 and v1
+            cpu.load_rom([0x80, inst].to_vec()).unwrap();
+            cpu.reg[0xf] = 0x1;
+            cpu.step().unwrap();
+            assert_eq!(cpu.reg[0xf], if quirk { 0x0 } else { 0x1 });
+        }
+    }
+
+    #[test]
+    fn test_clip_quirk() {
+        let rom: Vec<u8> = [
+            // address 0x200
+            // draw a 1 row sprite at v0, v1
+            0xd0, 0x11,
+        ]
+        .to_vec();
+        for (clip, wrapped) in [(true, 0x0), (false, 0x1)] {
+            let mut cpu = Cpu::new();
+            cpu.set_quirks(Quirks {
+                clip_sprites: clip,
+                ..Quirks::default()
+            });
+            cpu.load_rom(rom.clone()).unwrap();
+            cpu.i = 0x300;
+            cpu.ram[0x300] = 0xff;
+            // the start position always wraps
+            cpu.reg[0] = 60 + 64;
+            cpu.reg[1] = 3;
+            cpu.step().unwrap();
+            assert_eq!(cpu.pixel(63, 3), 0x1);
+            assert_eq!(cpu.pixel(0, 3), wrapped);
+        }
+    }
+
+    #[test]
+    fn test_display_wait_quirk() {
+        let mut cpu = Cpu::with_platform(Platform::CosmacVip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // draw an empty sprite twice
+            0xd0, 0x01, // address 0x202
+            0xd0, 0x01,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        assert_eq!(cpu.step(), Ok(StepOutcome::Executed));
+        assert_eq!(cpu.step(), Ok(StepOutcome::WaitingForVblank));
+        assert_eq!(cpu.pc, 0x202);
+        cpu.tick_timers();
+        assert_eq!(cpu.step(), Ok(StepOutcome::Executed));
+        assert_eq!(cpu.pc, 0x204);
+    }
+
+    #[test]
+    fn test_superchip_1_0_cannot_scroll() {
+        for rom in [[0x00, 0xc1], [0x00, 0xfb], [0xf0, 0x30]] {
+            let mut cpu = Cpu::with_platform(Platform::SuperChip10);
+            cpu.load_rom(rom.to_vec()).unwrap();
+            assert!(cpu.step().is_err());
+        }
+    }
 }
@@ -0,0 +1,346 @@
+// save states, a snapshot of the whole machine in a versioned binary format
+//
+// every number is little endian
+//
+//  magic       4 bytes  "C8SS"
+//  version     u16
+//  rom hash    40 bytes lowercase hex sha-1, zeros when no rom was loaded
+//  platform    u8
+//  rng mode    u8
+//  ram         u32 length, then the bytes
+//  vram        128 x 64 bytes, column by column
+//  hires, planes, 16 registers, i (u16), pc (u16), 16 stack slots (u16),
+//  sp, dt, st, 16 keypad keys, rng state (u64), 16 rpl flags, vblank,
+//  audio pattern (a u8 set when present, then 16 bytes), pitch
+
+use super::{Cpu, HIRES_HEIGHT, HIRES_WIDTH, PROGRAM_START};
+use crate::bytes::Reader;
+use crate::platform::Platform;
+use crate::rng::RngMode;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+const VERSION: u16 = 2;
+const HASH_LEN: usize = 40;
+
+const PLATFORMS: [Platform; 6] = [
+    Platform::Chip8,
+    Platform::CosmacVip,
+    Platform::Chip48,
+    Platform::SuperChip10,
+    Platform::SuperChip,
+    Platform::XoChip,
+];
+
+const RNG_MODES: [RngMode; 2] = [RngMode::Wyrand, RngMode::CosmacVip];
+
+impl Cpu {
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.ram.len() + HIRES_WIDTH * HIRES_HEIGHT + 256);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        match &self.rom_hash {
+            Some(hash) => out.extend_from_slice(hash.as_bytes()),
+            None => out.extend_from_slice(&[0x0; HASH_LEN]),
+        }
+        let platform = PLATFORMS.iter().position(|p| *p == self.platform);
+        out.push(platform.expect("every platform has a save state id") as u8);
+        let rng_mode = RNG_MODES.iter().position(|m| *m == self.rand.mode());
+        out.push(rng_mode.expect("every rng mode has a save state id") as u8);
+        out.extend_from_slice(&(self.ram.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.ram);
+        for column in self.vram.iter() {
+            out.extend_from_slice(column);
+        }
+        out.push(self.hires as u8);
+        out.push(self.planes);
+        out.extend_from_slice(&self.reg);
+        out.extend_from_slice(&self.i.to_le_bytes());
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        for slot in self.stack {
+            out.extend_from_slice(&slot.to_le_bytes());
+        }
+        out.push(self.sp);
+        out.push(self.dt);
+        out.push(self.st);
+        out.extend_from_slice(&self.keypad);
+        out.extend_from_slice(&self.rand.state().to_le_bytes());
+        out.extend_from_slice(&self.rpl);
+        out.push(self.vblank as u8);
+        match self.pattern {
+            Some(pattern) => {
+                out.push(0x1);
+                out.extend_from_slice(&pattern);
+            }
+            None => {
+                out.push(0x0);
+                out.extend_from_slice(&[0x0; 16]);
+            }
+        }
+        out.push(self.pitch);
+        out
+    }
+
+    // the cpu is left alone unless the whole state is valid
+    // a state saved for another rom, platform or rng mode is refused
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), String> {
+        let mut reader = Reader::new(state, "save state");
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err("not a save state".to_string());
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(format!(
+                "save state version {} is not supported, expected {}",
+                version, VERSION
+            ));
+        }
+        let hash = reader.bytes(HASH_LEN)?;
+        if let Some(rom_hash) = &self.rom_hash {
+            if hash.iter().any(|b| *b != 0x0) && hash != rom_hash.as_bytes() {
+                return Err("save state is for a different rom".to_string());
+            }
+        }
+        let platform = PLATFORMS
+            .get(reader.u8()? as usize)
+            .copied()
+            .ok_or("save state has an unknown platform")?;
+        if platform != self.platform {
+            return Err(format!(
+                "save state is for {}, running as {}",
+                platform, self.platform
+            ));
+        }
+        let rng_mode = RNG_MODES
+            .get(reader.u8()? as usize)
+            .copied()
+            .ok_or("save state has an unknown rng mode")?;
+        if rng_mode != self.rand.mode() {
+            return Err(format!(
+                "save state uses the {} rng, running with {}",
+                rng_mode,
+                self.rand.mode()
+            ));
+        }
+        let ram_len = reader.u32()? as usize;
+        if ram_len != self.ram.len() {
+            return Err(format!(
+                "save state has {} bytes of ram, expected {}",
+                ram_len,
+                self.ram.len()
+            ));
+        }
+        let ram = reader.bytes(ram_len)?;
+        let mut vram = [[0x0; HIRES_HEIGHT]; HIRES_WIDTH];
+        for column in vram.iter_mut() {
+            column.copy_from_slice(reader.bytes(HIRES_HEIGHT)?);
+        }
+        let hires = reader.u8()? != 0;
+        let planes = reader.u8()?;
+        let reg = reader.array::<0x10>()?;
+        let i = reader.u16()?;
+        let pc = reader.u16()?;
+        let mut stack = [0x0; 0x10];
+        for slot in stack.iter_mut() {
+            *slot = reader.u16()?;
+        }
+        // entries live at stack[1..=sp], 00ee reads stack[sp]
+        let sp = reader.u8()?;
+        if sp as usize >= stack.len() {
+            return Err(format!("save state has a stack pointer of {}", sp));
+        }
+        let dt = reader.u8()?;
+        let st = reader.u8()?;
+        let keypad = reader.array::<0x10>()?;
+        let rand = reader.u64()?;
+        let rpl = reader.array::<0x10>()?;
+        let vblank = reader.u8()? != 0;
+        let has_pattern = reader.u8()? != 0;
+        let pattern = reader.array::<16>()?;
+        let pitch = reader.u8()?;
+        if !reader.is_empty() {
+            return Err("save state has trailing bytes".to_string());
+        }
+
+        self.ram.copy_from_slice(ram);
+        self.vram = vram;
+        self.hires = hires;
+        self.planes = planes;
+        self.reg = reg;
+        self.i = i;
+        self.pc = pc;
+        self.stack = stack;
+        self.sp = sp;
+        self.dt = dt;
+        self.st = st;
+        self.keypad = keypad;
+        self.rand.set_state(rand);
+        self.rpl = rpl;
+        self.vblank = vblank;
+        self.pattern = has_pattern.then_some(pattern);
+        self.pitch = pitch;
+        Ok(())
+    }
+
+    // for a rom rebuilt in place, the state saved with the old build is loaded
+    // and the new build written over it, the rom hash is not checked
+    // the caller decides whether the two builds share a layout
+    pub fn load_state_over_rom(&mut self, state: &[u8], rom: &[u8]) -> Result<(), String> {
+        if rom.len() > self.ram.len() - PROGRAM_START {
+            return Err(format!("rom of {} bytes does not fit in ram", rom.len()));
+        }
+        let rom_hash = self.rom_hash.take();
+        let loaded = self.load_state(state);
+        self.rom_hash = rom_hash;
+        loaded?;
+        self.ram[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction;
+
+    fn running_cpu() -> Cpu {
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        let rom: Vec<u8> = [
+            // address 0x200
+            // random number into v0
+            0xc0, 0xff, // address 0x202
+            // draw a 5 row sprite at v1, v2
+            0xd1, 0x25, // address 0x204
+            // jump to 0x200
+            0x12, 0x00,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu.set_delay_timer(20);
+        cpu.set_key(0x3, true);
+        for _ in 0..5 {
+            cpu.step().unwrap();
+        }
+        cpu
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut cpu = running_cpu();
+        let state = cpu.save_state();
+        let pc = cpu.pc();
+        let registers = *cpu.registers();
+        let pixels: Vec<u8> = (0..8).map(|x| cpu.pixel(x, 0)).collect();
+
+        for _ in 0..7 {
+            cpu.step().unwrap();
+        }
+        let after = *cpu.registers();
+        cpu.set_delay_timer(0);
+        cpu.set_key(0x3, false);
+
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.pc(), pc);
+        assert_eq!(*cpu.registers(), registers);
+        assert_eq!(cpu.delay_timer(), 20);
+        assert!(cpu.key(0x3));
+        let restored: Vec<u8> = (0..8).map(|x| cpu.pixel(x, 0)).collect();
+        assert_eq!(restored, pixels);
+        assert_eq!(cpu.save_state(), state);
+
+        // the random numbers repeat too
+        for _ in 0..7 {
+            cpu.step().unwrap();
+        }
+        assert_eq!(*cpu.registers(), after);
+    }
+
+    #[test]
+    fn test_rng_mode() {
+        let mut vip = running_cpu();
+        vip.set_rng_mode(RngMode::CosmacVip);
+        vip.step().unwrap();
+        let state = vip.save_state();
+
+        let mut cpu = running_cpu();
+        cpu.set_rng_mode(RngMode::CosmacVip);
+        cpu.load_state(&state).unwrap();
+        assert_eq!(cpu.save_state(), state);
+        // the random numbers carry on from the same routine
+        for _ in 0..3 {
+            vip.step().unwrap();
+            cpu.step().unwrap();
+        }
+        assert_eq!(*cpu.registers(), *vip.registers());
+
+        let mut wyrand = running_cpu();
+        assert_eq!(
+            wyrand.load_state(&state),
+            Err("save state uses the vip rng, running with wyrand".to_string())
+        );
+    }
+
+    #[test]
+    fn test_header() {
+        let state = running_cpu().save_state();
+        assert_eq!(&state[0..4], b"C8SS");
+        assert_eq!(u16::from_le_bytes([state[4], state[5]]), VERSION);
+    }
+
+    #[test]
+    fn test_rejects_bad_states() {
+        let state = running_cpu().save_state();
+        let mut cpu = running_cpu();
+
+        assert_eq!(cpu.load_state(b"nope"), Err("not a save state".to_string()));
+        assert_eq!(
+            cpu.load_state(&state[..state.len() - 1]),
+            Err("save state is truncated".to_string())
+        );
+        let mut newer = state.clone();
+        newer[4] = 0x3;
+        assert!(cpu.load_state(&newer).is_err());
+
+        let mut other_rom = Cpu::with_platform(Platform::XoChip);
+        other_rom.load_rom([0x12, 0x00].to_vec()).unwrap();
+        assert_eq!(
+            other_rom.load_state(&state),
+            Err("save state is for a different rom".to_string())
+        );
+
+        let mut other_platform = Cpu::new();
+        assert!(other_platform.load_state(&state).is_err());
+        assert_eq!(other_platform.pc(), 0x200);
+
+        // sp is the byte before dt, st, 16 keys, the rng state, 16 flags,
+        // vblank, the pattern and pitch
+        let mut deep = state.clone();
+        let sp_at = state.len() - (1 + 1 + 1 + 16 + 8 + 16 + 1 + 17 + 1);
+        assert_eq!(deep[sp_at], cpu.sp);
+        deep[sp_at] = 15;
+        assert!(cpu.load_state(&deep).is_ok());
+        deep[sp_at] = 16;
+        assert_eq!(
+            cpu.load_state(&deep),
+            Err("save state has a stack pointer of 16".to_string())
+        );
+    }
+
+    #[test]
+    fn test_load_state_over_rom() {
+        let state = running_cpu().save_state();
+        let mut cpu = Cpu::with_platform(Platform::XoChip);
+        let rom = [0x60, 0x07, 0x12, 0x00];
+        cpu.load_rom(rom.to_vec()).unwrap();
+        let hash = cpu.rom_hash().map(str::to_string);
+
+        cpu.load_state_over_rom(&state, &rom).unwrap();
+        assert_eq!(cpu.pc(), running_cpu().pc());
+        assert_eq!(cpu.instruction_at(0x200), instruction::decode(0x6007).ok());
+        // the old build's bytes past the new rom are kept
+        assert_eq!(cpu.instruction_at(0x204), instruction::decode(0x1200).ok());
+        assert_eq!(cpu.rom_hash().map(str::to_string), hash);
+
+        let mut other_platform = Cpu::new();
+        assert!(other_platform.load_state_over_rom(&state, &rom).is_err());
+    }
+}
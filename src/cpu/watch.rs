@@ -0,0 +1,202 @@
+// memory watchpoints, every ram access an instruction makes through
+// read_ram, write_ram or the fetch in step is checked against them and the
+// ones that match are kept until taken, the instruction itself still runs
+
+use super::Cpu;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    // the instruction fetch
+    Exec,
+}
+
+// watches start through end, both included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub read: bool,
+    pub write: bool,
+    pub exec: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub access: Access,
+    pub addr: u16,
+    // the instruction that made the access
+    pub pc: u16,
+    pub opcode: u16,
+    // the same for reads and fetches
+    pub old: u8,
+    pub new: u8,
+}
+
+impl Watchpoint {
+    pub fn matches(&self, access: Access, addr: usize) -> bool {
+        let watched = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+            Access::Exec => self.exec,
+        };
+        watched && (self.start as usize..=self.end as usize).contains(&addr)
+    }
+}
+
+impl Cpu {
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    // removes the watchpoints starting at start, returns how many there were
+    pub fn remove_watchpoints(&mut self, start: u16) -> usize {
+        let before = self.watchpoints.len();
+        self.watchpoints
+            .retain(|watchpoint| watchpoint.start != start);
+        before - self.watchpoints.len()
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // the hits since the last call, in the order the accesses happened
+    pub fn take_watch_hits(&mut self) -> Vec<WatchHit> {
+        std::mem::take(&mut self.watch_hits)
+    }
+
+    pub(super) fn watch(&mut self, access: Access, addr: usize, old: u8, new: u8) {
+        if !self
+            .watchpoints
+            .iter()
+            .any(|watchpoint| watchpoint.matches(access, addr))
+        {
+            return;
+        }
+        let (pc, opcode) = self.executing;
+        self.watch_hits.push(WatchHit {
+            access,
+            addr: addr as u16,
+            pc,
+            opcode,
+            old,
+            new,
+        });
+    }
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match self {
+            Access::Read => "read",
+            Access::Write => "written",
+            Access::Exec => "executed",
+        };
+        write!(f, "{}", access)
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05x}", self.start)?;
+        if self.end != self.start {
+            write!(f, "-{:#05x}", self.end)?;
+        }
+        let flags = [(self.read, 'r'), (self.write, 'w'), (self.exec, 'x')];
+        let flags: String = flags
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, flag)| flag)
+            .collect();
+        write!(f, " {}", flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watch(start: u16, end: u16, flags: &str) -> Watchpoint {
+        Watchpoint {
+            start,
+            end,
+            read: flags.contains('r'),
+            write: flags.contains('w'),
+            exec: flags.contains('x'),
+        }
+    }
+
+    #[test]
+    fn test_write_hits() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(
+            [
+                // address 0x200
+                // set i to 0x300
+                0xa3, 0x00, // address 0x202
+                // set v2 to 0x05
+                0x62, 0x05, // address 0x204
+                // store v0 through v2 at i
+                0xf2, 0x55,
+            ]
+            .to_vec(),
+        )
+        .unwrap();
+        cpu.add_watchpoint(watch(0x302, 0x310, "w"));
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert!(cpu.take_watch_hits().is_empty());
+        cpu.step().unwrap();
+        assert_eq!(
+            cpu.take_watch_hits(),
+            vec![WatchHit {
+                access: Access::Write,
+                addr: 0x302,
+                pc: 0x204,
+                opcode: 0xf255,
+                old: 0x00,
+                new: 0x05,
+            }]
+        );
+        assert!(cpu.take_watch_hits().is_empty());
+    }
+
+    #[test]
+    fn test_read_and_exec_hits() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(
+            [
+                // address 0x200
+                // set i to 0x206
+                0xa2, 0x06, // address 0x202
+                // draw the byte at 0x206
+                0xd0, 0x01, // address 0x204
+                // jump to 0x204
+                0x12, 0x04, // address 0x206
+                0x80,
+            ]
+            .to_vec(),
+        )
+        .unwrap();
+        cpu.add_watchpoint(watch(0x206, 0x206, "r"));
+        cpu.add_watchpoint(watch(0x204, 0x204, "x"));
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        let hits = cpu.take_watch_hits();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].access, Access::Read);
+        assert_eq!((hits[0].pc, hits[0].old, hits[0].new), (0x202, 0x80, 0x80));
+        cpu.step().unwrap();
+        let hits = cpu.take_watch_hits();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].access, hits[0].addr), (Access::Exec, 0x204));
+
+        assert_eq!(cpu.remove_watchpoints(0x204), 1);
+        cpu.step().unwrap();
+        assert!(cpu.take_watch_hits().is_empty());
+        assert_eq!(watch(0x300, 0x30f, "rw").to_string(), "0x300-0x30f rw");
+    }
+}
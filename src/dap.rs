@@ -0,0 +1,1017 @@
+// debug adapter protocol server, so editors can debug a rom
+// https://microsoft.github.io/debug-adapter-protocol/specification
+//
+// messages are json behind a Content-Length header, requests are read on a
+// thread so the runner keeps drawing while it waits for them
+//
+// the launch request takes
+//
+//  program        the rom, files ending in .8o are octo source
+//  platform       chip8, vip, chip48, schip1.0, schip1.1 or xochip
+//  stopOnEntry    pause before the first instruction
+//  ipf            instructions per frame
+//
+// a rom without source is shown as a cowgod listing, breakpoints go on its
+// lines or on instruction addresses from the disassembly
+//
+// there is one thread, the registers are one scope and the timers another,
+// memory references are ram addresses like 0x200
+
+use crate::asm::SourceMap;
+use crate::debugger::{Breakpoint, Debugger};
+use crate::disasm::{self, Listing, Syntax};
+use crate::platform::Platform;
+use crate::reload::Build;
+use crate::{Cpu, CpuError};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+
+const THREAD_ID: u64 = 1;
+const REGISTERS_SCOPE: u64 = 1;
+const TIMERS_SCOPE: u64 = 2;
+// the listing of a rom without source
+const LISTING_REFERENCE: u64 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Launch {
+    pub program: String,
+    pub platform: Option<Platform>,
+    pub stop_on_entry: bool,
+    pub ipf: Option<u32>,
+}
+
+pub struct Session {
+    requests: Receiver<Result<Value, String>>,
+    output: Box<dyn Write>,
+    seq: u64,
+    // answered once the rom is loaded
+    launch: Option<Value>,
+    stop_on_entry: bool,
+    // the dap source frames and breakpoints refer to
+    source: Value,
+    path: Option<String>,
+    source_map: SourceMap,
+    // the listing shown for a rom without source
+    listing: Option<String>,
+    // names of the routines for stack frames
+    labels: BTreeMap<u16, String>,
+    source_breakpoints: BTreeSet<u16>,
+    instruction_breakpoints: BTreeSet<u16>,
+    // why the debugger is going to stop, a stopped event is owed while set
+    stopping: Option<&'static str>,
+}
+
+impl Session {
+    pub fn stdio() -> Self {
+        Self::new(std::io::stdin(), std::io::stdout())
+    }
+
+    pub fn new(input: impl Read + Send + 'static, output: impl Write + 'static) -> Self {
+        let (sender, requests) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut input = BufReader::new(input);
+            loop {
+                let message = match read_message(&mut input) {
+                    Ok(Some(message)) => Ok(message),
+                    Ok(None) => break,
+                    Err(e) => Err(e),
+                };
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Self {
+            requests,
+            output: Box::new(output),
+            seq: 0,
+            launch: None,
+            stop_on_entry: false,
+            source: Value::Null,
+            path: None,
+            source_map: SourceMap::default(),
+            listing: None,
+            labels: BTreeMap::new(),
+            source_breakpoints: BTreeSet::new(),
+            instruction_breakpoints: BTreeSet::new(),
+            stopping: None,
+        }
+    }
+
+    // answers initialize until the client asks to launch a rom
+    pub fn wait_for_launch(&mut self) -> Result<Launch, String> {
+        loop {
+            let request = match self.requests.recv() {
+                Ok(request) => request?,
+                Err(_) => return Err("the client went away before launching".to_string()),
+            };
+            let args = &request["arguments"];
+            match request["command"].as_str().unwrap_or("") {
+                "initialize" => self.respond(&request, Ok(capabilities()))?,
+                "launch" => {
+                    let Some(program) = args["program"].as_str() else {
+                        self.respond(&request, Err("launch needs a program".to_string()))?;
+                        continue;
+                    };
+                    let platform = match args["platform"].as_str().map(str::parse) {
+                        Some(Ok(platform)) => Some(platform),
+                        Some(Err(e)) => {
+                            self.respond(&request, Err(e))?;
+                            continue;
+                        }
+                        None => None,
+                    };
+                    let launch = Launch {
+                        program: program.to_string(),
+                        platform,
+                        stop_on_entry: args["stopOnEntry"].as_bool().unwrap_or(false),
+                        ipf: args["ipf"].as_u64().map(|ipf| ipf as u32),
+                    };
+                    self.stop_on_entry = launch.stop_on_entry;
+                    self.launch = Some(request);
+                    return Ok(launch);
+                }
+                "disconnect" | "terminate" => {
+                    self.respond(&request, Ok(Value::Null))?;
+                    return Err("the client disconnected before launching".to_string());
+                }
+                command => {
+                    self.respond(&request, Err(format!("{} before launch", command)))?;
+                }
+            }
+        }
+    }
+
+    // the rom is loaded, the client can set breakpoints now
+    pub fn launched(
+        &mut self,
+        program: &str,
+        build: &Build,
+        platform: Platform,
+    ) -> Result<(), String> {
+        let name = std::path::Path::new(program)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| program.to_string());
+        match &build.source_map {
+            Some(source_map) => {
+                let path = std::fs::canonicalize(program)
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .unwrap_or_else(|_| program.to_string());
+                self.source = json!({ "name": name, "path": path });
+                self.path = Some(path);
+                self.source_map = source_map.clone();
+                for (label, addr) in &build.labels {
+                    self.labels.entry(*addr).or_insert_with(|| label.clone());
+                }
+            }
+            None => {
+                let listing = Listing::new(&build.rom, platform);
+                self.source = json!({
+                    "name": format!("{} (disassembly)", name),
+                    "sourceReference": LISTING_REFERENCE,
+                });
+                self.source_map = listing.source_map(Syntax::Cowgod);
+                self.listing = Some(listing.render(Syntax::Cowgod));
+                self.labels = listing
+                    .labels
+                    .iter()
+                    .map(|addr| (*addr, format!("label_{:03x}", addr)))
+                    .collect();
+            }
+        }
+        if let Some(request) = self.launch.take() {
+            self.respond(&request, Ok(Value::Null))?;
+        }
+        self.event("initialized", Value::Null)
+    }
+
+    pub fn launch_failed(&mut self, message: &str) -> Result<(), String> {
+        match self.launch.take() {
+            Some(request) => self.respond(&request, Err(message.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    // handle every request since the last poll, true once the client is done
+    pub fn poll(&mut self, debugger: &mut Debugger, cpu: &mut Cpu) -> Result<bool, String> {
+        loop {
+            let request = match self.requests.try_recv() {
+                Ok(request) => request?,
+                Err(TryRecvError::Empty) => return Ok(false),
+                Err(TryRecvError::Disconnected) => return Ok(true),
+            };
+            let command = request["command"].as_str().unwrap_or("");
+            if matches!(command, "disconnect" | "terminate") {
+                self.respond(&request, Ok(Value::Null))?;
+                return Ok(true);
+            }
+            let result = self.request(command, &request["arguments"], debugger, cpu);
+            self.respond(&request, result)?;
+            // stopping on entry and pausing happen right away, after the response
+            if debugger.paused() {
+                self.stopped(None)?;
+            }
+        }
+    }
+
+    // the debugger stopped, the client hears why if it is waiting for that
+    pub fn stopped(&mut self, halted: Option<&CpuError>) -> Result<(), String> {
+        let Some(reason) = self.stopping.take() else {
+            return Ok(());
+        };
+        let body = match halted {
+            Some(error) => json!({
+                "reason": "exception",
+                "description": error.to_string(),
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+            None => json!({
+                "reason": reason,
+                "threadId": THREAD_ID,
+                "allThreadsStopped": true,
+            }),
+        };
+        self.event("stopped", body)
+    }
+
+    // the rom ran 00fd
+    pub fn exited(&mut self) -> Result<(), String> {
+        self.stopping = None;
+        self.event("exited", json!({ "exitCode": 0 }))?;
+        self.event("terminated", Value::Null)
+    }
+
+    // shown in the client's debug console
+    pub fn output(&mut self, text: &str) -> Result<(), String> {
+        self.event("output", json!({ "category": "console", "output": text }))
+    }
+
+    fn request(
+        &mut self,
+        command: &str,
+        args: &Value,
+        debugger: &mut Debugger,
+        cpu: &mut Cpu,
+    ) -> Result<Value, String> {
+        match command {
+            "initialize" => Ok(capabilities()),
+            "setBreakpoints" => Ok(self.set_breakpoints(args, debugger)),
+            "setInstructionBreakpoints" => {
+                Ok(self.set_instruction_breakpoints(args, debugger, cpu))
+            }
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    self.stopping = Some("entry");
+                } else {
+                    debugger.run(cpu);
+                    self.stopping = Some("breakpoint");
+                }
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "chip-8" }] })),
+            "stackTrace" => Ok(self.stack_trace(args, cpu)),
+            "scopes" => Ok(json!({
+                "scopes": [
+                    { "name": "Registers", "variablesReference": REGISTERS_SCOPE, "expensive": false },
+                    { "name": "Timers", "variablesReference": TIMERS_SCOPE, "expensive": false },
+                ]
+            })),
+            "variables" => Ok(json!({ "variables": variables(args, cpu) })),
+            "continue" => {
+                debugger.run(cpu);
+                self.stopping = Some("breakpoint");
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                debugger.step_over(cpu);
+                self.stopping = Some("step");
+                Ok(Value::Null)
+            }
+            "stepIn" => {
+                debugger.step(1, cpu);
+                self.stopping = Some("step");
+                Ok(Value::Null)
+            }
+            "stepOut" => {
+                debugger.step_out(cpu)?;
+                self.stopping = Some("step");
+                Ok(Value::Null)
+            }
+            "pause" => {
+                debugger.pause();
+                self.stopping = Some("pause");
+                Ok(Value::Null)
+            }
+            "readMemory" => read_memory(args, cpu),
+            "disassemble" => self.disassemble(args, cpu),
+            "source" => match (&self.listing, args["sourceReference"].as_u64()) {
+                (Some(listing), Some(LISTING_REFERENCE)) => Ok(json!({ "content": listing })),
+                _ => Err("no such source".to_string()),
+            },
+            _ => Err(format!("{} is not supported", command)),
+        }
+    }
+
+    fn set_breakpoints(&mut self, args: &Value, debugger: &mut Debugger) -> Value {
+        let ours = match (&args["source"]["sourceReference"], &args["source"]["path"]) {
+            (Value::Number(reference), _) if self.listing.is_some() => {
+                reference.as_u64() == Some(LISTING_REFERENCE)
+            }
+            (_, Value::String(path)) => {
+                std::fs::canonicalize(path)
+                    .map(|path| path.to_string_lossy().into_owned())
+                    .ok()
+                    == self.path
+            }
+            _ => false,
+        };
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let mut addrs = BTreeSet::new();
+        let breakpoints: Vec<Value> = requested
+            .iter()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+                match self.source_map.addr(line).filter(|_| ours) {
+                    Some((addr, line)) => {
+                        addrs.insert(addr);
+                        json!({
+                            "verified": true,
+                            "line": line,
+                            "source": self.source,
+                            "instructionReference": reference(addr),
+                        })
+                    }
+                    None => json!({
+                        "verified": false,
+                        "line": line,
+                        "message": "no code on or after this line",
+                    }),
+                }
+            })
+            .collect();
+        if ours {
+            let old = std::mem::replace(&mut self.source_breakpoints, addrs);
+            self.sync_breakpoints(&old, debugger);
+        }
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_instruction_breakpoints(
+        &mut self,
+        args: &Value,
+        debugger: &mut Debugger,
+        cpu: &Cpu,
+    ) -> Value {
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let mut addrs = BTreeSet::new();
+        let breakpoints: Vec<Value> = requested
+            .iter()
+            .map(|breakpoint| {
+                let addr = breakpoint["instructionReference"]
+                    .as_str()
+                    .and_then(parse_reference)
+                    .map(|addr| addr + breakpoint["offset"].as_i64().unwrap_or(0))
+                    .filter(|addr| (0..cpu.ram().len() as i64).contains(addr));
+                match addr {
+                    Some(addr) => {
+                        addrs.insert(addr as u16);
+                        json!({ "verified": true, "instructionReference": reference(addr as u16) })
+                    }
+                    None => json!({ "verified": false, "message": "not a ram address" }),
+                }
+            })
+            .collect();
+        let old = std::mem::replace(&mut self.instruction_breakpoints, addrs);
+        self.sync_breakpoints(&old, debugger);
+        json!({ "breakpoints": breakpoints })
+    }
+
+    // both kinds of breakpoints end up in the debugger, once per address
+    fn sync_breakpoints(&self, old: &BTreeSet<u16>, debugger: &mut Debugger) {
+        for addr in old {
+            debugger.remove_breakpoints(*addr);
+        }
+        for addr in self.source_breakpoints.union(&self.instruction_breakpoints) {
+            debugger.remove_breakpoints(*addr);
+            debugger.add_breakpoint(Breakpoint {
+                addr: *addr,
+                condition: None,
+            });
+        }
+    }
+
+    // the instruction about to run, then the call of every subroutine on the stack
+    fn stack_trace(&self, args: &Value, cpu: &Cpu) -> Value {
+        let calls = (1..=cpu.sp() as usize)
+            .rev()
+            .filter_map(|slot| cpu.stack().get(slot))
+            .map(|ret| ret.wrapping_sub(2));
+        let frames: Vec<Value> = std::iter::once(cpu.pc())
+            .chain(calls)
+            .enumerate()
+            .map(|(id, addr)| self.frame(id, addr))
+            .collect();
+        let total = frames.len();
+        let start = args["startFrame"].as_u64().unwrap_or(0) as usize;
+        let levels = match args["levels"].as_u64() {
+            Some(0) | None => total,
+            Some(levels) => levels as usize,
+        };
+        let frames: Vec<Value> = frames.into_iter().skip(start).take(levels).collect();
+        json!({ "stackFrames": frames, "totalFrames": total })
+    }
+
+    fn frame(&self, id: usize, addr: u16) -> Value {
+        let name = self
+            .labels
+            .range(..=addr)
+            .next_back()
+            .map(|(_, label)| label.clone())
+            .unwrap_or_else(|| reference(addr));
+        let mut frame = json!({
+            "id": id,
+            "name": name,
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": reference(addr),
+        });
+        if let Some(line) = self.source_map.line(addr) {
+            frame["line"] = json!(line);
+            frame["column"] = json!(1);
+            frame["source"] = self.source.clone();
+        }
+        frame
+    }
+
+    // instructions are taken to be two bytes, addresses outside ram are ??
+    fn disassemble(&self, args: &Value, cpu: &Cpu) -> Result<Value, String> {
+        let ram = cpu.ram();
+        let base = args["memoryReference"]
+            .as_str()
+            .and_then(parse_reference)
+            .ok_or("disassemble needs a memory reference")?;
+        let start = clamp(base, ram)
+            + clamp(args["offset"].as_i64().unwrap_or(0), ram)
+            + clamp(args["instructionOffset"].as_i64().unwrap_or(0), ram) * 2;
+        let count = clamp(args["instructionCount"].as_i64().unwrap_or(0), ram).max(0);
+        let instructions: Vec<Value> = (0..count)
+            .map(|n| start + n * 2)
+            .map(|addr| {
+                if !(0..ram.len() as i64).contains(&addr) {
+                    return json!({
+                        "address": format!("{:#05x}", addr.rem_euclid(0x10000)),
+                        "instruction": "??",
+                        "presentationHint": "invalid",
+                    });
+                }
+                let bytes = &ram[addr as usize..(addr as usize + 2).min(ram.len())];
+                let mut instruction = json!({
+                    "address": reference(addr as u16),
+                    "instructionBytes": bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" "),
+                    "instruction": disasm::disassemble_at(ram, addr as usize),
+                });
+                if let Some(line) = self.source_map.line(addr as u16) {
+                    instruction["line"] = json!(line);
+                    instruction["location"] = self.source.clone();
+                }
+                if let Some(label) = self.labels.get(&(addr as u16)) {
+                    instruction["symbol"] = json!(label);
+                }
+                instruction
+            })
+            .collect();
+        Ok(json!({ "instructions": instructions }))
+    }
+
+    fn respond(&mut self, request: &Value, result: Result<Value, String>) -> Result<(), String> {
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": result.is_ok(),
+        });
+        match result {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = json!(message),
+        }
+        self.send(response)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Result<(), String> {
+        let mut message = json!({ "type": "event", "event": event });
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        self.send(message)
+    }
+
+    fn send(&mut self, mut message: Value) -> Result<(), String> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.output, &message)
+    }
+}
+
+fn capabilities() -> Value {
+    json!({
+        "supportsConfigurationDoneRequest": true,
+        "supportsReadMemoryRequest": true,
+        "supportsDisassembleRequest": true,
+        "supportsInstructionBreakpoints": true,
+        "supportsTerminateRequest": true,
+    })
+}
+
+fn variables(args: &Value, cpu: &Cpu) -> Vec<Value> {
+    let byte = |name: String, value: u8| json!({ "name": name, "value": format!("{:#04x}", value), "type": "u8", "variablesReference": 0 });
+    // addresses can be opened in a memory view
+    let address = |name: &str, value: u16| {
+        json!({
+            "name": name,
+            "value": reference(value),
+            "type": "u16",
+            "variablesReference": 0,
+            "memoryReference": reference(value),
+        })
+    };
+    match args["variablesReference"].as_u64() {
+        Some(REGISTERS_SCOPE) => {
+            let mut variables: Vec<Value> = cpu
+                .registers()
+                .iter()
+                .enumerate()
+                .map(|(x, value)| byte(format!("v{:x}", x), *value))
+                .collect();
+            variables.push(address("i", cpu.i()));
+            variables.push(address("pc", cpu.pc()));
+            variables.push(byte("sp".to_string(), cpu.sp()));
+            variables
+        }
+        Some(TIMERS_SCOPE) => vec![
+            byte("dt".to_string(), cpu.delay_timer()),
+            byte("st".to_string(), cpu.sound_timer()),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+fn read_memory(args: &Value, cpu: &Cpu) -> Result<Value, String> {
+    let ram = cpu.ram();
+    let base = args["memoryReference"]
+        .as_str()
+        .and_then(parse_reference)
+        .ok_or("readMemory needs a memory reference")?;
+    let start = clamp(base, ram) + clamp(args["offset"].as_i64().unwrap_or(0), ram);
+    let count = clamp(args["count"].as_i64().unwrap_or(0), ram).max(0) as usize;
+    if !(0..ram.len() as i64).contains(&start) {
+        return Ok(json!({
+            "address": format!("{:#05x}", start.rem_euclid(0x10000)),
+            "unreadableBytes": count,
+        }));
+    }
+    let start = start as usize;
+    let end = (start + count).min(ram.len());
+    Ok(json!({
+        "address": reference(start as u16),
+        "data": base64(&ram[start..end]),
+        "unreadableBytes": count - (end - start),
+    }))
+}
+
+// whatever a client sends is cut down to the size of ram before any arithmetic
+fn clamp(n: i64, ram: &[u8]) -> i64 {
+    let len = ram.len() as i64;
+    n.clamp(-len, len)
+}
+
+fn reference(addr: u16) -> String {
+    format!("{:#05x}", addr)
+}
+
+// hex with or without 0x
+fn parse_reference(text: &str) -> Option<i64> {
+    i64::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |bits, (n, b)| bits | (*b as u32) << (16 - n * 8));
+        for n in 0..4 {
+            if n <= chunk.len() {
+                out.push(ALPHABET[(bits >> (18 - n * 6)) as usize & 0x3f] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// none once the input is closed
+pub fn read_message(input: &mut impl BufRead) -> Result<Option<Value>, String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        let read = input
+            .read_line(&mut header)
+            .map_err(|e| format!("unable to read a message: {}", e))?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = Some(
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|e| format!("bad Content-Length {}: {}", value.trim(), e))?,
+            );
+        }
+    }
+    let length = length.ok_or("message without a Content-Length")?;
+    let mut body = vec![0x0; length];
+    input
+        .read_exact(&mut body)
+        .map_err(|e| format!("unable to read a message: {}", e))?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| format!("message is not json: {}", e))
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> Result<(), String> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|()| output.flush())
+        .map_err(|e| format!("unable to write a message: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::null::{NullAudio, NullInput, NullVideo};
+    use crate::frontend::InputEvent;
+    use crate::movie::Movie;
+    use crate::runner::Runner;
+    use crate::scheduler::Scheduler;
+    use std::collections::VecDeque;
+    use std::io::PipeWriter;
+    use std::time::{Duration, Instant};
+
+    // a scripted editor on the other end of a pair of pipes, messages are
+    // read on a thread like the session reads requests
+    struct Client {
+        requests: PipeWriter,
+        messages: Receiver<Value>,
+        inbox: VecDeque<Value>,
+        seq: u64,
+    }
+
+    impl Client {
+        fn new() -> (Self, Session) {
+            let (request_reader, requests) = std::io::pipe().unwrap();
+            let (message_reader, message_writer) = std::io::pipe().unwrap();
+            let (sender, messages) = mpsc::channel();
+            std::thread::spawn(move || {
+                let mut message_reader = BufReader::new(message_reader);
+                while let Ok(Some(message)) = read_message(&mut message_reader) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            });
+            let client = Self {
+                requests,
+                messages,
+                inbox: VecDeque::new(),
+                seq: 0,
+            };
+            (client, Session::new(request_reader, message_writer))
+        }
+
+        fn send(&mut self, command: &str, arguments: Value) {
+            self.seq += 1;
+            let request = json!({
+                "seq": self.seq,
+                "type": "request",
+                "command": command,
+                "arguments": arguments,
+            });
+            write_message(&mut self.requests, &request).unwrap();
+        }
+
+        // polls the runner until count messages came back
+        fn pump(&mut self, runner: &mut Runner<NullVideo, NullAudio, NullInput>, count: usize) {
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while self.inbox.len() < count {
+                assert!(Instant::now() < deadline, "the session went quiet");
+                runner.handle_input().unwrap();
+                if let Ok(message) = self.messages.recv_timeout(Duration::from_millis(1)) {
+                    self.inbox.push_back(message);
+                }
+            }
+        }
+
+        fn receive(&mut self) -> Value {
+            match self.inbox.pop_front() {
+                Some(message) => message,
+                None => self.messages.recv_timeout(Duration::from_secs(5)).unwrap(),
+            }
+        }
+
+        fn response(&mut self, command: &str) -> Value {
+            let response = self.receive();
+            assert_eq!(response["type"], "response");
+            assert_eq!(response["command"], command);
+            assert_eq!(response["success"], true, "{}", response);
+            response["body"].clone()
+        }
+
+        fn event(&mut self, event: &str) -> Value {
+            let message = self.receive();
+            assert_eq!(message["type"], "event");
+            assert_eq!(message["event"], event, "{}", message);
+            message["body"].clone()
+        }
+    }
+
+    fn runner(
+        build: &Build,
+        session: Session,
+        script: Vec<Vec<InputEvent>>,
+    ) -> Runner<NullVideo, NullAudio, NullInput> {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(build.rom.clone()).unwrap();
+        let mut runner = Runner::new(
+            cpu,
+            Scheduler::new(4),
+            build.rom.clone(),
+            NullVideo::default(),
+            NullAudio::default(),
+            NullInput::new(script),
+        );
+        runner.set_dap(Debugger::new(), session);
+        runner
+    }
+
+    const SOURCE: &str = "\
+: main
+  v1 := 1
+  sub
+  jump main
+: sub
+  v2 += 1
+  return
+";
+
+    #[test]
+    fn test_source_session() {
+        let dir = std::env::temp_dir().join(format!("chip8-dap-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.8o");
+        std::fs::write(&path, SOURCE).unwrap();
+        let program = path.to_str().unwrap();
+
+        let (mut client, mut session) = Client::new();
+        client.send("initialize", json!({ "adapterID": "chip8" }));
+        client.send("launch", json!({ "program": program, "stopOnEntry": true }));
+        let launch = session.wait_for_launch().unwrap();
+        assert!(client.response("initialize")["supportsReadMemoryRequest"] == true);
+        assert_eq!(launch.program, program);
+        assert!(launch.stop_on_entry);
+        let build = Build::from_file(program, Platform::Chip8).unwrap();
+        session.launched(program, &build, Platform::Chip8).unwrap();
+        client.response("launch");
+        client.event("initialized");
+        let mut runner = runner(&build, session, vec![]);
+
+        // the label line breaks on the instruction under it
+        client.send(
+            "setBreakpoints",
+            json!({ "source": { "path": program }, "breakpoints": [{ "line": 5 }, { "line": 9 }] }),
+        );
+        client.send("configurationDone", json!({}));
+        client.pump(&mut runner, 3);
+        let breakpoints = client.response("setBreakpoints")["breakpoints"].clone();
+        assert_eq!(breakpoints[0]["verified"], true);
+        assert_eq!(breakpoints[0]["line"], 6);
+        assert_eq!(breakpoints[0]["instructionReference"], "0x206");
+        assert_eq!(breakpoints[1]["verified"], false);
+        client.response("configurationDone");
+        assert_eq!(client.event("stopped")["reason"], "entry");
+
+        client.send("stackTrace", json!({ "threadId": 1 }));
+        client.pump(&mut runner, 1);
+        let frames = client.response("stackTrace")["stackFrames"].clone();
+        assert_eq!(frames[0]["name"], "main");
+        assert_eq!(frames[0]["line"], 2);
+
+        client.send("continue", json!({ "threadId": 1 }));
+        client.pump(&mut runner, 1);
+        client.response("continue");
+        runner.run_frames(1).unwrap();
+        assert_eq!(client.event("stopped")["reason"], "breakpoint");
+        assert_eq!(runner.cpu.pc(), 0x206);
+
+        client.send("stackTrace", json!({ "threadId": 1 }));
+        client.send("scopes", json!({ "frameId": 0 }));
+        client.send(
+            "variables",
+            json!({ "variablesReference": REGISTERS_SCOPE }),
+        );
+        client.pump(&mut runner, 3);
+        let trace = client.response("stackTrace");
+        assert_eq!(trace["totalFrames"], 2);
+        let frames = trace["stackFrames"].clone();
+        assert_eq!(frames[0]["name"], "sub");
+        assert_eq!(frames[0]["line"], 6);
+        assert_eq!(frames[1]["name"], "main");
+        assert_eq!(frames[1]["line"], 3);
+        assert_eq!(frames[1]["instructionPointerReference"], "0x202");
+        assert_eq!(frames[1]["source"]["name"], "game.8o");
+        let scopes = client.response("scopes")["scopes"].clone();
+        assert_eq!(scopes[1]["name"], "Timers");
+        let variables = client.response("variables")["variables"].clone();
+        assert_eq!(variables[1]["name"], "v1");
+        assert_eq!(variables[1]["value"], "0x01");
+        assert_eq!(variables[17]["name"], "pc");
+        assert_eq!(variables[17]["memoryReference"], "0x206");
+
+        client.send("stepOut", json!({ "threadId": 1 }));
+        client.pump(&mut runner, 1);
+        client.response("stepOut");
+        runner.run_frames(1).unwrap();
+        assert_eq!(client.event("stopped")["reason"], "step");
+        assert_eq!(runner.cpu.pc(), 0x204);
+        assert_eq!(runner.cpu.registers()[2], 1);
+
+        client.send("stepOut", json!({ "threadId": 1 }));
+        client.send(
+            "readMemory",
+            json!({ "memoryReference": "0x200", "offset": 2, "count": 4 }),
+        );
+        client.pump(&mut runner, 2);
+        let failed = client.receive();
+        assert_eq!(failed["success"], false);
+        assert_eq!(failed["message"], "not inside a subroutine");
+        let memory = client.response("readMemory");
+        assert_eq!(memory["address"], "0x202");
+        // 22 06 12 00
+        assert_eq!(memory["data"], "IgYSAA==");
+        assert_eq!(memory["unreadableBytes"], 0);
+
+        client.send("disconnect", json!({}));
+        client.pump(&mut runner, 1);
+        client.response("disconnect");
+        assert!(runner.quit());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_disassembly_session() {
+        let build = Build {
+            rom: [
+                // address 0x200
+                // add 1 to v1
+                0x71, 0x01, // address 0x202
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            source_map: None,
+            labels: BTreeMap::new(),
+        };
+        let (mut client, mut session) = Client::new();
+        client.send(
+            "launch",
+            json!({ "program": "loop.ch8", "platform": "schip" }),
+        );
+        let launch = session.wait_for_launch().unwrap();
+        assert_eq!(launch.platform, Some(Platform::SuperChip));
+        assert!(!launch.stop_on_entry);
+        session
+            .launched("loop.ch8", &build, Platform::Chip8)
+            .unwrap();
+        client.response("launch");
+        client.event("initialized");
+        let mut runner = runner(&build, session, vec![]);
+
+        client.send("source", json!({ "sourceReference": LISTING_REFERENCE }));
+        client.send(
+            "setBreakpoints",
+            json!({ "source": { "sourceReference": LISTING_REFERENCE }, "breakpoints": [{ "line": 2 }] }),
+        );
+        client.send(
+            "setInstructionBreakpoints",
+            json!({ "breakpoints": [{ "instructionReference": "0x200", "offset": 2 }] }),
+        );
+        client.send(
+            "disassemble",
+            json!({ "memoryReference": "0x200", "instructionOffset": -1, "instructionCount": 3 }),
+        );
+        client.send("configurationDone", json!({}));
+        client.pump(&mut runner, 5);
+        let source = client.response("source");
+        assert_eq!(
+            source["content"],
+            "label_200:\n0x200  71 01        add v1, #01\n0x202  12 00        jp label_200\n"
+        );
+        let breakpoints = client.response("setBreakpoints")["breakpoints"].clone();
+        assert_eq!(breakpoints[0]["instructionReference"], "0x200");
+        let breakpoints = client.response("setInstructionBreakpoints")["breakpoints"].clone();
+        assert_eq!(breakpoints[0]["instructionReference"], "0x202");
+        assert_eq!(runner.debugger().unwrap().breakpoints().len(), 2);
+        let instructions = client.response("disassemble")["instructions"].clone();
+        assert_eq!(instructions[0]["address"], "0x1fe");
+        assert_eq!(instructions[1]["instruction"], "add v1, #01");
+        assert_eq!(instructions[1]["symbol"], "label_200");
+        assert_eq!(instructions[2]["line"], 3);
+        // not stopping on entry, the rom runs until the listing breakpoint
+        client.response("configurationDone");
+        runner.run_frames(1).unwrap();
+        assert_eq!(client.event("stopped")["reason"], "breakpoint");
+        assert_eq!(runner.cpu.pc(), 0x202);
+
+        // clearing the listing breakpoints keeps the instruction one
+        client.send(
+            "setBreakpoints",
+            json!({ "source": { "sourceReference": LISTING_REFERENCE }, "breakpoints": [] }),
+        );
+        client.send("pause", json!({ "threadId": 1 }));
+        client.pump(&mut runner, 3);
+        client.response("setBreakpoints");
+        assert_eq!(runner.debugger().unwrap().breakpoints().len(), 1);
+        client.response("pause");
+        assert_eq!(client.event("stopped")["reason"], "pause");
+
+        // counts and offsets past the end of ram are cut down, not overflowed
+        client.send(
+            "readMemory",
+            json!({ "memoryReference": "0xfff", "offset": i64::MAX, "count": i64::MAX }),
+        );
+        client.send(
+            "disassemble",
+            json!({
+                "memoryReference": "0x7fffffffffffffff",
+                "offset": i64::MAX,
+                "instructionOffset": i64::MAX,
+                "instructionCount": i64::MAX,
+            }),
+        );
+        client.pump(&mut runner, 2);
+        let memory = client.response("readMemory");
+        assert_eq!(memory["unreadableBytes"], 0x1000);
+        let instructions = client.response("disassemble")["instructions"].clone();
+        assert_eq!(instructions.as_array().unwrap().len(), 0x1000);
+        assert_eq!(instructions[0]["presentationHint"], "invalid");
+    }
+
+    #[test]
+    fn test_status_messages() {
+        let build = Build {
+            rom: [
+                // address 0x200
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            source_map: None,
+            labels: BTreeMap::new(),
+        };
+        let (mut client, session) = Client::new();
+        let mut runner = runner(&build, session, vec![vec![InputEvent::ToggleMute]]);
+        runner.handle_input().unwrap();
+        // stdout carries the protocol, so the message is an output event
+        assert_eq!(client.event("output")["output"], "sound muted....\n");
+
+        let mut movie = Movie::new(&runner.cpu, 4).unwrap();
+        movie.finish(&runner.cpu);
+        runner.play(movie);
+        runner.run_frames(1).unwrap();
+        assert_eq!(
+            client.event("output")["output"],
+            "playback finished, the end state matches....\n"
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
@@ -0,0 +1,792 @@
+// interactive debugger, driven by commands typed at a terminal prompt while
+// the frontend keeps drawing and polling input
+//
+//  s, step [n]         run n instructions, 1 by default
+//  n, next             step over a call
+//  o, out              run until the current subroutine returns
+//  c, continue         run until a breakpoint
+//  b, break <addr> [if <reg> <op> <value>]
+//                      stop before the instruction at addr, only when the
+//                      condition holds if there is one
+//  d, delete <addr>    remove the breakpoints at addr
+//  w, watch <addr>[-<end>] [rwx]
+//                      stop after an instruction reads (r), writes (w) or
+//                      executes (x) memory in the range, w by default
+//  u, unwatch <addr>   remove the watchpoints starting at addr
+//  l, list             list the breakpoints and watchpoints
+//  r, regs             print the registers again
+//  q, quit             quit the emulator
+//  h, help
+//
+// addresses and values are hex, with or without 0x
+// registers are v0 - vf, i, dt, st and sp, ops are == != < <= > >=
+// an empty line repeats the last command
+
+use crate::asm::SourceMap;
+use crate::cpu::Watchpoint;
+use crate::disasm;
+use crate::instruction::Instruction;
+use crate::Cpu;
+use std::fmt;
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver};
+
+pub const PROMPT: &str = "(c8db) ";
+
+const HELP: &str = "\
+s, step [n]         run n instructions, 1 by default
+n, next             step over a call
+o, out              run until the current subroutine returns
+c, continue         run until a breakpoint
+b, break <addr> [if <reg> <op> <value>]
+d, delete <addr>    remove the breakpoints at addr
+w, watch <addr>[-<end>] [rwx]
+u, unwatch <addr>   remove the watchpoints starting at addr
+l, list             list the breakpoints and watchpoints
+r, regs             print the registers
+q, quit             quit the emulator
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(usize),
+    I,
+    Dt,
+    St,
+    Sp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub register: Register,
+    pub compare: Compare,
+    pub value: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub addr: u16,
+    pub condition: Option<Condition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    // instructions left to run
+    Step(u32),
+    // stop once the call at pc returns to the instruction after it
+    StepOver { pc: u16, sp: u8 },
+    // stop once the stack is shallower than sp
+    StepOut { sp: u8 },
+}
+
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    last_command: String,
+    // breakpoints at this address are skipped once, so continuing from a
+    // breakpoint gets past it
+    resume_from: Option<u16>,
+    quit: bool,
+    // source lines shown instead of disassembly for assembled programs
+    source_map: Option<SourceMap>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    // starts out paused
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            mode: Mode::Paused,
+            last_command: String::new(),
+            resume_from: None,
+            source_map: None,
+            quit: false,
+        }
+    }
+
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = Some(source_map);
+    }
+
+    // the registers, stack and the instruction about to run
+    pub fn describe(&self, cpu: &Cpu) -> String {
+        describe(cpu, self.source_map.as_ref())
+    }
+
+    pub fn paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    // called before every instruction, true when the debugger stops there
+    pub fn check(&mut self, cpu: &Cpu) -> bool {
+        let pc = cpu.pc();
+        let resumed = self.resume_from == Some(pc);
+        let stop = match self.mode {
+            Mode::Paused => true,
+            Mode::Running => false,
+            Mode::Step(left) => left == 0,
+            Mode::StepOver { pc: after, sp } => pc == after && cpu.sp() == sp,
+            Mode::StepOut { sp } => cpu.sp() < sp,
+        };
+        if stop || (!resumed && self.breakpoint_hit(cpu)) {
+            self.mode = Mode::Paused;
+            return true;
+        }
+        false
+    }
+
+    // called after every instruction that ran, the report of the
+    // watchpoints it hit when the debugger stops because of them
+    // the instruction resumed from ran, so a breakpoint on it counts again
+    pub fn executed(&mut self, cpu: &mut Cpu) -> Option<String> {
+        self.resume_from = None;
+        if let Mode::Step(left) = &mut self.mode {
+            *left = left.saturating_sub(1);
+        }
+        let hits = cpu.take_watch_hits();
+        if hits.is_empty() {
+            return None;
+        }
+        self.mode = Mode::Paused;
+        let report = hits
+            .iter()
+            .map(|hit| {
+                format!(
+                    "watchpoint {:#05x} {} by {:#05x}  {}: {:#04x} -> {:#04x}\n",
+                    hit.addr,
+                    hit.access,
+                    hit.pc,
+                    self.source_map
+                        .as_ref()
+                        .and_then(|source_map| source_map.describe(hit.pc))
+                        .unwrap_or_else(|| disasm::disassemble(hit.opcode)),
+                    hit.old,
+                    hit.new
+                )
+            })
+            .collect();
+        Some(report)
+    }
+
+    fn breakpoint_hit(&self, cpu: &Cpu) -> bool {
+        self.breakpoints.iter().any(|breakpoint| {
+            breakpoint.addr == cpu.pc()
+                && breakpoint
+                    .condition
+                    .is_none_or(|condition| condition.holds(cpu))
+        })
+    }
+
+    fn resume(&mut self, mode: Mode, cpu: &Cpu) {
+        self.mode = mode;
+        self.resume_from = Some(cpu.pc());
+    }
+
+    // run until a breakpoint
+    pub fn run(&mut self, cpu: &Cpu) {
+        self.resume(Mode::Running, cpu);
+    }
+
+    // run count instructions
+    pub fn step(&mut self, count: u32, cpu: &Cpu) {
+        self.resume(Mode::Step(count), cpu);
+    }
+
+    // like step, but a call runs until it returns
+    pub fn step_over(&mut self, cpu: &Cpu) {
+        let mode = if let Some(Instruction::Call(_)) = cpu.instruction_at(cpu.pc()) {
+            Mode::StepOver {
+                pc: cpu.pc().wrapping_add(2),
+                sp: cpu.sp(),
+            }
+        } else {
+            Mode::Step(1)
+        };
+        self.resume(mode, cpu);
+    }
+
+    // run until the current subroutine returns
+    pub fn step_out(&mut self, cpu: &Cpu) -> Result<(), String> {
+        if cpu.sp() == 0 {
+            return Err("not inside a subroutine".to_string());
+        }
+        self.resume(Mode::StepOut { sp: cpu.sp() }, cpu);
+        Ok(())
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    // returns how many there were at addr
+    pub fn remove_breakpoints(&mut self, addr: u16) -> usize {
+        let before = self.breakpoints.len();
+        self.breakpoints
+            .retain(|breakpoint| breakpoint.addr != addr);
+        before - self.breakpoints.len()
+    }
+
+    // run one command, returns what to print
+    pub fn command(&mut self, line: &str, cpu: &mut Cpu) -> String {
+        let line = match line.trim() {
+            "" => self.last_command.clone(),
+            line => line.to_string(),
+        };
+        self.last_command = line.clone();
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return String::new();
+        };
+        let args: Vec<&str> = words.collect();
+        match self.run_command(command, &args, cpu) {
+            Ok(output) => output,
+            Err(e) => format!("{}\n", e),
+        }
+    }
+
+    fn run_command(
+        &mut self,
+        command: &str,
+        args: &[&str],
+        cpu: &mut Cpu,
+    ) -> Result<String, String> {
+        match command {
+            "s" | "step" => {
+                let count = match args.first() {
+                    Some(count) => count
+                        .parse::<u32>()
+                        .map_err(|_| format!("{} is not a number of instructions", count))?,
+                    None => 1,
+                };
+                self.step(count, cpu);
+                Ok(String::new())
+            }
+            "n" | "next" => {
+                self.step_over(cpu);
+                Ok(String::new())
+            }
+            "o" | "out" => {
+                self.step_out(cpu)?;
+                Ok(String::new())
+            }
+            "c" | "continue" => {
+                self.run(cpu);
+                Ok(String::new())
+            }
+            "b" | "break" => {
+                let breakpoint = parse_breakpoint(args)?;
+                self.add_breakpoint(breakpoint);
+                Ok(format!("breakpoint {}\n", breakpoint))
+            }
+            "d" | "delete" => {
+                let addr = parse_hex(args.first().ok_or("delete needs an address")?)?;
+                Ok(format!(
+                    "deleted {} breakpoints\n",
+                    self.remove_breakpoints(addr)
+                ))
+            }
+            "w" | "watch" => {
+                let watchpoint = parse_watchpoint(args)?;
+                cpu.add_watchpoint(watchpoint);
+                Ok(format!("watchpoint {}\n", watchpoint))
+            }
+            "u" | "unwatch" => {
+                let addr = parse_hex(args.first().ok_or("unwatch needs an address")?)?;
+                Ok(format!(
+                    "deleted {} watchpoints\n",
+                    cpu.remove_watchpoints(addr)
+                ))
+            }
+            "l" | "list" => {
+                if self.breakpoints.is_empty() && cpu.watchpoints().is_empty() {
+                    return Ok("no breakpoints or watchpoints\n".to_string());
+                }
+                let breakpoints = self
+                    .breakpoints
+                    .iter()
+                    .map(|breakpoint| format!("breakpoint {}\n", breakpoint));
+                let watchpoints = cpu
+                    .watchpoints()
+                    .iter()
+                    .map(|watchpoint| format!("watchpoint {}\n", watchpoint));
+                Ok(breakpoints.chain(watchpoints).collect())
+            }
+            "r" | "regs" => Ok(self.describe(cpu)),
+            "q" | "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            "h" | "help" => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command {}, try help", command)),
+        }
+    }
+}
+
+// the registers, stack and the instruction about to run, its source line
+// when there is a source map
+pub fn describe(cpu: &Cpu, source_map: Option<&SourceMap>) -> String {
+    let instruction = source_map
+        .and_then(|source_map| source_map.describe(cpu.pc()))
+        .unwrap_or_else(|| disasm::disassemble_at(cpu.ram(), cpu.pc() as usize));
+    let mut out = format!("{:#05x}  {}\n", cpu.pc(), instruction);
+    for row in cpu.registers().chunks(8).enumerate() {
+        let (row, registers) = row;
+        let registers: Vec<String> = registers
+            .iter()
+            .enumerate()
+            .map(|(n, value)| format!("v{:x} {:02x}", row * 8 + n, value))
+            .collect();
+        out += &registers.join("  ");
+        out += "\n";
+    }
+    out += &format!(
+        "i {:#05x}  sp {}  dt {:02x}  st {:02x}\n",
+        cpu.i(),
+        cpu.sp(),
+        cpu.delay_timer(),
+        cpu.sound_timer()
+    );
+    let stack: Vec<String> = cpu.stack()[1..=cpu.sp() as usize]
+        .iter()
+        .map(|addr| format!("{:#05x}", addr))
+        .collect();
+    out += &format!("stack [{}]\n", stack.join(" "));
+    out
+}
+
+// lines typed at the terminal, read on their own thread so the frontend
+// keeps running while waiting for a command
+pub fn stdin_commands() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+impl Register {
+    fn value(&self, cpu: &Cpu) -> u16 {
+        match self {
+            Register::V(x) => cpu.registers()[*x] as u16,
+            Register::I => cpu.i(),
+            Register::Dt => cpu.delay_timer() as u16,
+            Register::St => cpu.sound_timer() as u16,
+            Register::Sp => cpu.sp() as u16,
+        }
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        let value = self.register.value(cpu);
+        match self.compare {
+            Compare::Eq => value == self.value,
+            Compare::Ne => value != self.value,
+            Compare::Lt => value < self.value,
+            Compare::Le => value <= self.value,
+            Compare::Gt => value > self.value,
+            Compare::Ge => value >= self.value,
+        }
+    }
+}
+
+fn parse_hex(text: &str) -> Result<u16, String> {
+    let digits = text.strip_prefix("0x").unwrap_or(text);
+    u16::from_str_radix(digits, 16).map_err(|_| format!("{} is not a hex number", text))
+}
+
+fn parse_register(text: &str) -> Result<Register, String> {
+    match text.to_lowercase().as_str() {
+        "i" => Ok(Register::I),
+        "dt" => Ok(Register::Dt),
+        "st" => Ok(Register::St),
+        "sp" => Ok(Register::Sp),
+        name => match name.strip_prefix('v') {
+            Some(x) if x.len() == 1 => usize::from_str_radix(x, 16)
+                .map(Register::V)
+                .map_err(|_| format!("unknown register {}", text)),
+            _ => Err(format!("unknown register {}", text)),
+        },
+    }
+}
+
+fn parse_compare(text: &str) -> Result<Compare, String> {
+    match text {
+        "==" => Ok(Compare::Eq),
+        "!=" => Ok(Compare::Ne),
+        "<" => Ok(Compare::Lt),
+        "<=" => Ok(Compare::Le),
+        ">" => Ok(Compare::Gt),
+        ">=" => Ok(Compare::Ge),
+        _ => Err(format!("unknown comparison {}", text)),
+    }
+}
+
+fn parse_breakpoint(args: &[&str]) -> Result<Breakpoint, String> {
+    match args {
+        [addr] => Ok(Breakpoint {
+            addr: parse_hex(addr)?,
+            condition: None,
+        }),
+        [addr, "if", register, compare, value] => Ok(Breakpoint {
+            addr: parse_hex(addr)?,
+            condition: Some(Condition {
+                register: parse_register(register)?,
+                compare: parse_compare(compare)?,
+                value: parse_hex(value)?,
+            }),
+        }),
+        _ => Err("expected break <addr> [if <reg> <op> <value>]".to_string()),
+    }
+}
+
+fn parse_watchpoint(args: &[&str]) -> Result<Watchpoint, String> {
+    let (range, flags) = match args {
+        [range] => (*range, "w"),
+        [range, flags] => (*range, *flags),
+        _ => return Err("expected watch <addr>[-<end>] [rwx]".to_string()),
+    };
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (parse_hex(start)?, parse_hex(end)?),
+        None => (parse_hex(range)?, parse_hex(range)?),
+    };
+    if end < start {
+        return Err(format!("{} ends before it starts", range));
+    }
+    if flags.is_empty() || !flags.chars().all(|flag| "rwx".contains(flag)) {
+        return Err(format!("{} is not a mix of r, w and x", flags));
+    }
+    Ok(Watchpoint {
+        start,
+        end,
+        read: flags.contains('r'),
+        write: flags.contains('w'),
+        exec: flags.contains('x'),
+    })
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "v{:x}", x),
+            Register::I => write!(f, "i"),
+            Register::Dt => write!(f, "dt"),
+            Register::St => write!(f, "st"),
+            Register::Sp => write!(f, "sp"),
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Compare::Eq => "==",
+            Compare::Ne => "!=",
+            Compare::Lt => "<",
+            Compare::Le => "<=",
+            Compare::Gt => ">",
+            Compare::Ge => ">=",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#05x}", self.addr)?;
+        if let Some(condition) = &self.condition {
+            write!(
+                f,
+                " if {} {} {:#x}",
+                condition.register, condition.compare, condition.value
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+    use crate::Platform;
+    use crate::StepOutcome;
+
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        let rom: Vec<u8> = [
+            // address 0x200
+            // call 0x208
+            0x22, 0x08, // address 0x202
+            // add 1 to v0
+            0x70, 0x01, // address 0x204
+            // jump to 0x200
+            0x12, 0x00, // address 0x206
+            0x00, 0x00, // address 0x208
+            // add 1 to v1
+            0x71, 0x01, // address 0x20a
+            // add 1 to v2
+            0x72, 0x01, // address 0x20c
+            // return
+            0x00, 0xee,
+        ]
+        .to_vec();
+        cpu.load_rom(rom).unwrap();
+        cpu
+    }
+
+    // run until the debugger stops, at most limit instructions
+    fn run(debugger: &mut Debugger, cpu: &mut Cpu, limit: usize) {
+        for _ in 0..limit {
+            if debugger.check(cpu) {
+                return;
+            }
+            cpu.step().unwrap();
+            if debugger.executed(cpu).is_some() {
+                return;
+            }
+        }
+        panic!("the debugger never stopped");
+    }
+
+    #[test]
+    fn test_step() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        assert!(debugger.check(&cpu));
+        debugger.command("step", &mut cpu);
+        run(&mut debugger, &mut cpu, 10);
+        assert_eq!(cpu.pc(), 0x208);
+        debugger.command("s 2", &mut cpu);
+        run(&mut debugger, &mut cpu, 10);
+        assert_eq!(cpu.pc(), 0x20c);
+        // an empty line repeats the step, through the return
+        debugger.command("", &mut cpu);
+        run(&mut debugger, &mut cpu, 10);
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn test_next_and_out() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.command("next", &mut cpu);
+        run(&mut debugger, &mut cpu, 10);
+        assert_eq!(cpu.pc(), 0x202);
+        assert_eq!(cpu.registers()[1], 1);
+
+        debugger.command("s 2", &mut cpu);
+        run(&mut debugger, &mut cpu, 10);
+        debugger.command("s", &mut cpu);
+        run(&mut debugger, &mut cpu, 10);
+        assert_eq!(cpu.pc(), 0x208);
+        debugger.command("out", &mut cpu);
+        run(&mut debugger, &mut cpu, 10);
+        assert_eq!(cpu.pc(), 0x202);
+        assert_eq!(cpu.sp(), 0);
+        assert_eq!(
+            debugger.command("out", &mut cpu),
+            "not inside a subroutine\n"
+        );
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.command("b 20a", &mut cpu);
+        debugger.command("c", &mut cpu);
+        run(&mut debugger, &mut cpu, 100);
+        assert_eq!(cpu.pc(), 0x20a);
+        // continuing gets past the breakpoint it stopped on
+        debugger.command("c", &mut cpu);
+        run(&mut debugger, &mut cpu, 100);
+        assert_eq!(cpu.pc(), 0x20a);
+        assert_eq!(cpu.registers()[0], 1);
+        assert_eq!(
+            debugger.command("d 0x20a", &mut cpu),
+            "deleted 1 breakpoints\n"
+        );
+        assert_eq!(
+            debugger.command("list", &mut cpu),
+            "no breakpoints or watchpoints\n"
+        );
+    }
+
+    #[test]
+    fn test_resume_from_a_waiting_instruction() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(
+            [
+                // address 0x200
+                // wait for a key into v0
+                0xf0, 0x0a, // address 0x202
+                // jump to 0x202
+                0x12, 0x02,
+            ]
+            .to_vec(),
+        )
+        .unwrap();
+        let mut debugger = Debugger::new();
+        debugger.command("b 200", &mut cpu);
+        assert!(debugger.check(&cpu));
+        debugger.command("c", &mut cpu);
+        // the wait runs nothing, the breakpoint stays skipped until it does
+        for _ in 0..3 {
+            assert!(!debugger.check(&cpu));
+            assert_eq!(cpu.step(), Ok(StepOutcome::WaitingForKey));
+        }
+        cpu.set_key(0x5, true);
+        assert!(!debugger.check(&cpu));
+        assert_eq!(cpu.step(), Ok(StepOutcome::Executed));
+        assert_eq!(debugger.executed(&mut cpu), None);
+        assert_eq!(cpu.registers()[0], 0x5);
+    }
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.command("break 0x204 if v0 >= 3", &mut cpu),
+            "breakpoint 0x204 if v0 >= 0x3\n"
+        );
+        debugger.command("continue", &mut cpu);
+        run(&mut debugger, &mut cpu, 100);
+        assert_eq!(cpu.pc(), 0x204);
+        assert_eq!(cpu.registers()[0], 3);
+    }
+
+    #[test]
+    fn test_watchpoints() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        // v1 is never stored, but the subroutine is executed
+        assert_eq!(
+            debugger.command("watch 20a-20c x", &mut cpu),
+            "watchpoint 0x20a-0x20c x\n"
+        );
+        debugger.command("c", &mut cpu);
+        run(&mut debugger, &mut cpu, 100);
+        assert_eq!(cpu.pc(), 0x20c);
+        assert_eq!(
+            debugger.command("l", &mut cpu),
+            "watchpoint 0x20a-0x20c x\n"
+        );
+        assert_eq!(
+            debugger.command("unwatch 20a", &mut cpu),
+            "deleted 1 watchpoints\n"
+        );
+        assert_eq!(
+            debugger.command("w 300 rq", &mut cpu),
+            "rq is not a mix of r, w and x\n"
+        );
+        assert_eq!(
+            debugger.command("w 300-2ff", &mut cpu),
+            "300-2ff ends before it starts\n"
+        );
+    }
+
+    #[test]
+    fn test_watch_report() {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(
+            [
+                // address 0x200
+                // set i to 0x200
+                0xa2, 0x00, // address 0x202
+                // store the bcd of v0 over the code at i
+                0xf0, 0x33,
+            ]
+            .to_vec(),
+        )
+        .unwrap();
+        let mut debugger = Debugger::new();
+        debugger.command("w 200-2ff", &mut cpu);
+        debugger.command("c", &mut cpu);
+        cpu.step().unwrap();
+        assert_eq!(debugger.executed(&mut cpu), None);
+        cpu.step().unwrap();
+        assert_eq!(
+            debugger.executed(&mut cpu).unwrap(),
+            "watchpoint 0x202 written by 0x202  ld b, v0: 0xf0 -> 0x00\n\
+             watchpoint 0x201 written by 0x202  ld b, v0: 0x00 -> 0x00\n\
+             watchpoint 0x200 written by 0x202  ld b, v0: 0xa2 -> 0x00\n"
+        );
+        assert!(debugger.paused());
+    }
+
+    #[test]
+    fn test_bad_commands() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        assert_eq!(
+            debugger.command("jump", &mut cpu),
+            "unknown command jump, try help\n"
+        );
+        assert_eq!(
+            debugger.command("b 200 if vz == 1", &mut cpu),
+            "unknown register vz\n"
+        );
+        assert_eq!(
+            debugger.command("b xyz", &mut cpu),
+            "xyz is not a hex number\n"
+        );
+        assert!(debugger.paused());
+        debugger.command("quit", &mut cpu);
+        assert!(debugger.quit_requested());
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut cpu = cpu();
+        cpu.step().unwrap();
+        let text = describe(&cpu, None);
+        assert!(text.starts_with("0x208  add v1, #01\n"));
+        assert!(text.contains("v8 00"));
+        assert!(text.contains("stack [0x202]"));
+
+        let program = assemble(": main v1 := 1\n  main", Platform::Chip8).unwrap();
+        let mut cpu = Cpu::new();
+        cpu.load_rom(program.rom).unwrap();
+        cpu.step().unwrap();
+        let mut debugger = Debugger::new();
+        debugger.set_source_map(program.source_map);
+        assert!(debugger.describe(&cpu).starts_with("0x202  line 2: main\n"));
+    }
+}
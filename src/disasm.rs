@@ -0,0 +1,423 @@
+// disassembler, cowgod's mnemonics or octo's syntax
+// http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
+// https://github.com/JohnEarnest/Octo/blob/gh-pages/docs/Manual.md
+
+use crate::asm::SourceMap;
+use crate::cpu::PROGRAM_START;
+use crate::instruction::{self, Instruction};
+use crate::platform::Platform;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    #[default]
+    Cowgod,
+    Octo,
+}
+
+// the instruction at addr, f000 nnnn reads the word after it
+pub fn disassemble_at(ram: &[u8], addr: usize) -> String {
+    match word(ram, addr) {
+        Some(0xf000) => match word(ram, addr + 2) {
+            Some(nnnn) => format!("ld i, #{:04x}", nnnn),
+            None => disassemble(0xf000),
+        },
+        Some(opcode) => disassemble(opcode),
+        None => "??".to_string(),
+    }
+}
+
+// cowgod's mnemonic for any opcode of any platform
+pub fn disassemble(opcode: u16) -> String {
+    match instruction::decode(opcode) {
+        Ok(instruction) => format(instruction, Syntax::Cowgod, &|nnn| {
+            address(nnn, Syntax::Cowgod)
+        }),
+        Err(opcode) => bytes(opcode, Syntax::Cowgod),
+    }
+}
+
+fn word(ram: &[u8], at: usize) -> Option<u16> {
+    Some((*ram.get(at)? as u16) << 8 | *ram.get(at + 1)? as u16)
+}
+
+fn address(nnn: u16, syntax: Syntax) -> String {
+    match syntax {
+        Syntax::Cowgod => format!("#{:03x}", nnn),
+        Syntax::Octo => format!("0x{:03x}", nnn),
+    }
+}
+
+fn bytes(opcode: u16, syntax: Syntax) -> String {
+    let (msb, lsb) = (opcode >> 8, opcode & 0xff);
+    match syntax {
+        Syntax::Cowgod => format!("db #{:02x}, #{:02x}", msb, lsb),
+        Syntax::Octo => format!("0x{:02x} 0x{:02x}", msb, lsb),
+    }
+}
+
+// one instruction, target names the addresses of jumps and calls
+pub fn format(instruction: Instruction, syntax: Syntax, target: &dyn Fn(u16) -> String) -> String {
+    match syntax {
+        Syntax::Cowgod => cowgod(instruction, target),
+        Syntax::Octo => octo(instruction, target),
+    }
+}
+
+fn cowgod(instruction: Instruction, target: &dyn Fn(u16) -> String) -> String {
+    use Instruction::*;
+    match instruction {
+        Sys(nnn) => format!("sys #{:03x}", nnn),
+        Cls => "cls".to_string(),
+        Ret => "ret".to_string(),
+        ScrollDown(n) => format!("scd {}", n),
+        ScrollUp(n) => format!("scu {}", n),
+        ScrollRight => "scr".to_string(),
+        ScrollLeft => "scl".to_string(),
+        Exit => "exit".to_string(),
+        Low => "low".to_string(),
+        High => "high".to_string(),
+        Jump(nnn) => format!("jp {}", target(nnn)),
+        Call(nnn) => format!("call {}", target(nnn)),
+        SkipEqImm { x, kk } => format!("se v{:x}, #{:02x}", x, kk),
+        SkipNeImm { x, kk } => format!("sne v{:x}, #{:02x}", x, kk),
+        SkipEq { x, y } => format!("se v{:x}, v{:x}", x, y),
+        StoreRange { x, y } => format!("ld [i], v{:x} - v{:x}", x, y),
+        LoadRange { x, y } => format!("ld v{:x} - v{:x}, [i]", x, y),
+        LoadImm { x, kk } => format!("ld v{:x}, #{:02x}", x, kk),
+        AddImm { x, kk } => format!("add v{:x}, #{:02x}", x, kk),
+        Move { x, y } => format!("ld v{:x}, v{:x}", x, y),
+        Or { x, y } => format!("or v{:x}, v{:x}", x, y),
+        And { x, y } => format!("and v{:x}, v{:x}", x, y),
+        Xor { x, y } => format!("xor v{:x}, v{:x}", x, y),
+        Add { x, y } => format!("add v{:x}, v{:x}", x, y),
+        Sub { x, y } => format!("sub v{:x}, v{:x}", x, y),
+        ShiftRight { x, y } => format!("shr v{:x}, v{:x}", x, y),
+        SubN { x, y } => format!("subn v{:x}, v{:x}", x, y),
+        ShiftLeft { x, y } => format!("shl v{:x}, v{:x}", x, y),
+        SkipNe { x, y } => format!("sne v{:x}, v{:x}", x, y),
+        LoadI(nnn) => format!("ld i, #{:03x}", nnn),
+        JumpV0(nnn) => format!("jp v0, {}", target(nnn)),
+        Random { x, kk } => format!("rnd v{:x}, #{:02x}", x, kk),
+        Draw { x, y, n } => format!("drw v{:x}, v{:x}, {}", x, y, n),
+        SkipKey(x) => format!("skp v{:x}", x),
+        SkipNotKey(x) => format!("sknp v{:x}", x),
+        LoadILong => "ld i, long".to_string(),
+        Plane(n) => format!("plane {}", n),
+        Audio => "audio".to_string(),
+        LoadDelay(x) => format!("ld v{:x}, dt", x),
+        WaitKey(x) => format!("ld v{:x}, k", x),
+        SetDelay(x) => format!("ld dt, v{:x}", x),
+        SetSound(x) => format!("ld st, v{:x}", x),
+        AddI(x) => format!("add i, v{:x}", x),
+        Font(x) => format!("ld f, v{:x}", x),
+        BigFont(x) => format!("ld hf, v{:x}", x),
+        Bcd(x) => format!("ld b, v{:x}", x),
+        Pitch(x) => format!("pitch v{:x}", x),
+        Store(x) => format!("ld [i], v{:x}", x),
+        Load(x) => format!("ld v{:x}, [i]", x),
+        SaveFlags(x) => format!("ld r, v{:x}", x),
+        LoadFlags(x) => format!("ld v{:x}, r", x),
+    }
+}
+
+// skips become octo's conditionals, which name when the next instruction runs
+fn octo(instruction: Instruction, target: &dyn Fn(u16) -> String) -> String {
+    use Instruction::*;
+    match instruction {
+        Sys(nnn) => bytes(nnn, Syntax::Octo),
+        Cls => "clear".to_string(),
+        Ret => "return".to_string(),
+        ScrollDown(n) => format!("scroll-down {}", n),
+        ScrollUp(n) => format!("scroll-up {}", n),
+        ScrollRight => "scroll-right".to_string(),
+        ScrollLeft => "scroll-left".to_string(),
+        Exit => "exit".to_string(),
+        Low => "lores".to_string(),
+        High => "hires".to_string(),
+        Jump(nnn) => format!("jump {}", target(nnn)),
+        Call(nnn) => format!(":call {}", target(nnn)),
+        SkipEqImm { x, kk } => format!("if v{:x} != 0x{:02x} then", x, kk),
+        SkipNeImm { x, kk } => format!("if v{:x} == 0x{:02x} then", x, kk),
+        SkipEq { x, y } => format!("if v{:x} != v{:x} then", x, y),
+        StoreRange { x, y } => format!("save v{:x} - v{:x}", x, y),
+        LoadRange { x, y } => format!("load v{:x} - v{:x}", x, y),
+        LoadImm { x, kk } => format!("v{:x} := 0x{:02x}", x, kk),
+        AddImm { x, kk } => format!("v{:x} += 0x{:02x}", x, kk),
+        Move { x, y } => format!("v{:x} := v{:x}", x, y),
+        Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+        And { x, y } => format!("v{:x} &= v{:x}", x, y),
+        Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+        Add { x, y } => format!("v{:x} += v{:x}", x, y),
+        Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+        ShiftRight { x, y } => format!("v{:x} >>= v{:x}", x, y),
+        SubN { x, y } => format!("v{:x} =- v{:x}", x, y),
+        ShiftLeft { x, y } => format!("v{:x} <<= v{:x}", x, y),
+        SkipNe { x, y } => format!("if v{:x} == v{:x} then", x, y),
+        LoadI(nnn) => format!("i := 0x{:03x}", nnn),
+        JumpV0(nnn) => format!("jump0 {}", target(nnn)),
+        Random { x, kk } => format!("v{:x} := random 0x{:02x}", x, kk),
+        Draw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+        SkipKey(x) => format!("if v{:x} -key then", x),
+        SkipNotKey(x) => format!("if v{:x} key then", x),
+        LoadILong => "i := long".to_string(),
+        Plane(n) => format!("plane {}", n),
+        Audio => "audio".to_string(),
+        LoadDelay(x) => format!("v{:x} := delay", x),
+        WaitKey(x) => format!("v{:x} := key", x),
+        SetDelay(x) => format!("delay := v{:x}", x),
+        SetSound(x) => format!("buzzer := v{:x}", x),
+        AddI(x) => format!("i += v{:x}", x),
+        Font(x) => format!("i := hex v{:x}", x),
+        BigFont(x) => format!("i := bighex v{:x}", x),
+        Bcd(x) => format!("bcd v{:x}", x),
+        Pitch(x) => format!("pitch := v{:x}", x),
+        Store(x) => format!("save v{:x}", x),
+        Load(x) => format!("load v{:x}", x),
+        SaveFlags(x) => format!("saveflags v{:x}", x),
+        LoadFlags(x) => format!("loadflags v{:x}", x),
+    }
+}
+
+// one line of a rom listing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    pub bytes: Vec<u8>,
+    // none for bytes that are not an instruction on the platform
+    pub instruction: Option<Instruction>,
+    // the address after f000
+    pub long: Option<u16>,
+}
+
+// a whole rom swept from the start, two bytes at a time
+pub struct Listing {
+    pub lines: Vec<Line>,
+    // jump and call targets that start a line
+    pub labels: BTreeSet<u16>,
+}
+
+impl Listing {
+    pub fn new(rom: &[u8], platform: Platform) -> Self {
+        let mut lines = Vec::new();
+        let mut offset = 0;
+        while offset < rom.len() {
+            let addr = (PROGRAM_START + offset) as u16;
+            let decoded = word(rom, offset).map(|opcode| instruction::decode_for(platform, opcode));
+            let line = match decoded {
+                Some(Ok(Instruction::LoadILong)) if offset + 4 <= rom.len() => Line {
+                    addr,
+                    bytes: rom[offset..offset + 4].to_vec(),
+                    instruction: Some(Instruction::LoadILong),
+                    long: word(rom, offset + 2),
+                },
+                Some(Ok(instruction)) if instruction != Instruction::LoadILong => Line {
+                    addr,
+                    bytes: rom[offset..offset + 2].to_vec(),
+                    instruction: Some(instruction),
+                    long: None,
+                },
+                _ => Line {
+                    addr,
+                    bytes: rom[offset..(offset + 2).min(rom.len())].to_vec(),
+                    instruction: None,
+                    long: None,
+                },
+            };
+            offset += line.bytes.len();
+            lines.push(line);
+        }
+        let starts: BTreeSet<u16> = lines.iter().map(|line| line.addr).collect();
+        let labels = lines
+            .iter()
+            .filter_map(|line| line.instruction?.target())
+            .filter(|target| starts.contains(target))
+            .collect();
+        Self { lines, labels }
+    }
+
+    pub fn render(&self, syntax: Syntax) -> String {
+        self.render_lines(syntax).0
+    }
+
+    // the listing as source, so a debugger can show lines of it
+    pub fn source_map(&self, syntax: Syntax) -> SourceMap {
+        let (text, lines) = self.render_lines(syntax);
+        SourceMap::new(&text, lines)
+    }
+
+    // the listing and the line each address is on
+    fn render_lines(&self, syntax: Syntax) -> (String, BTreeMap<u16, usize>) {
+        let target = |nnn: u16| match self.labels.contains(&nnn) {
+            true => label(nnn),
+            false => address(nnn, syntax),
+        };
+        let mut out = String::new();
+        let mut lines = BTreeMap::new();
+        let mut number = 0;
+        for line in &self.lines {
+            if self.labels.contains(&line.addr) {
+                number += 1;
+                match syntax {
+                    Syntax::Cowgod => out += &format!("{}:\n", label(line.addr)),
+                    Syntax::Octo => out += &format!(": {}\n", label(line.addr)),
+                }
+            }
+            let text = match (line.instruction, line.long) {
+                (Some(Instruction::LoadILong), Some(nnnn)) => match syntax {
+                    Syntax::Cowgod => format!("ld i, #{:04x}", nnnn),
+                    Syntax::Octo => format!("i := long 0x{:04x}", nnnn),
+                },
+                (Some(instruction), _) => format(instruction, syntax, &target),
+                (None, _) => match (syntax, line.bytes.as_slice()) {
+                    (Syntax::Cowgod, [byte]) => format!("db #{:02x}", byte),
+                    (Syntax::Octo, [byte]) => format!("0x{:02x}", byte),
+                    (_, _) => bytes((line.bytes[0] as u16) << 8 | line.bytes[1] as u16, syntax),
+                },
+            };
+            number += 1;
+            for offset in 0..line.bytes.len() {
+                lines.insert(line.addr + offset as u16, number);
+            }
+            let raw: Vec<String> = line
+                .bytes
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect();
+            // octo keeps the address and bytes in a comment so the listing assembles again
+            match syntax {
+                Syntax::Cowgod => {
+                    out += &format!("{:#05x}  {:<12} {}\n", line.addr, raw.join(" "), text)
+                }
+                Syntax::Octo => {
+                    out += &format!("    {:<24} # {:#05x}  {}\n", text, line.addr, raw.join(" "))
+                }
+            }
+        }
+        (out, lines)
+    }
+}
+
+fn label(addr: u16) -> String {
+    format!("label_{:03x}", addr)
+}
+
+impl FromStr for Syntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cowgod" => Ok(Syntax::Cowgod),
+            "octo" => Ok(Syntax::Octo),
+            _ => Err(format!("unknown syntax {}, expected cowgod or octo", s)),
+        }
+    }
+}
+
+impl fmt::Display for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Syntax::Cowgod => write!(f, "cowgod"),
+            Syntax::Octo => write!(f, "octo"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(disassemble(0x00e0), "cls");
+        assert_eq!(disassemble(0x2345), "call #345");
+        assert_eq!(disassemble(0x8ab4), "add va, vb");
+        assert_eq!(disassemble(0xd125), "drw v1, v2, 5");
+        assert_eq!(disassemble(0xf355), "ld [i], v3");
+        assert_eq!(disassemble(0x5121), "db #51, #21");
+    }
+
+    #[test]
+    fn test_disassemble_at() {
+        let ram = [0xf0, 0x00, 0x12, 0x34, 0x00];
+        assert_eq!(disassemble_at(&ram, 0), "ld i, #1234");
+        assert_eq!(disassemble_at(&ram, 2), "jp #234");
+        assert_eq!(disassemble_at(&ram, 4), "??");
+    }
+
+    const ROM: [u8; 9] = [
+        // address 0x200
+        // call 0x206
+        0x22, 0x06, // address 0x202
+        // skip if v1 is 5
+        0x31, 0x05, // address 0x204
+        // jump to 0x202
+        0x12, 0x02, // address 0x206
+        // high, only on super-chip
+        0x00, 0xff, // address 0x208
+        0xaa,
+    ];
+
+    #[test]
+    fn test_listing_cowgod() {
+        let listing = Listing::new(&ROM, Platform::Chip8);
+        assert_eq!(listing.labels, BTreeSet::from([0x202, 0x206]));
+        assert_eq!(
+            listing.render(Syntax::Cowgod),
+            "0x200  22 06        call label_206\n\
+             label_202:\n\
+             0x202  31 05        se v1, #05\n\
+             0x204  12 02        jp label_202\n\
+             label_206:\n\
+             0x206  00 ff        db #00, #ff\n\
+             0x208  aa           db #aa\n"
+        );
+    }
+
+    #[test]
+    fn test_listing_source_map() {
+        let source_map = Listing::new(&ROM, Platform::Chip8).source_map(Syntax::Cowgod);
+        assert_eq!(source_map.line(0x200), Some(1));
+        assert_eq!(source_map.line(0x203), Some(3));
+        assert_eq!(
+            source_map.describe(0x206),
+            Some("line 6: 0x206  00 ff        db #00, #ff".to_string())
+        );
+        // a label line breaks on the instruction under it
+        assert_eq!(source_map.addr(2), Some((0x202, 3)));
+        assert_eq!(source_map.addr(8), None);
+    }
+
+    #[test]
+    fn test_listing_octo() {
+        let listing = Listing::new(&ROM, Platform::SuperChip);
+        assert_eq!(
+            listing.render(Syntax::Octo),
+            "    :call label_206          # 0x200  22 06\n\
+             : label_202\n    \
+             if v1 != 0x05 then       # 0x202  31 05\n    \
+             jump label_202           # 0x204  12 02\n\
+             : label_206\n    \
+             hires                    # 0x206  00 ff\n    \
+             0xaa                     # 0x208  aa\n"
+        );
+    }
+
+    #[test]
+    fn test_listing_long() {
+        let rom = [0xf0, 0x00, 0x12, 0x34, 0x1f, 0xff];
+        let listing = Listing::new(&rom, Platform::XoChip);
+        assert_eq!(listing.lines.len(), 2);
+        assert_eq!(listing.lines[0].long, Some(0x1234));
+        // a jump to somewhere not in the listing keeps its address
+        assert_eq!(
+            listing.render(Syntax::Octo),
+            "    i := long 0x1234         # 0x200  f0 00 12 34\n    \
+             jump 0xfff               # 0x204  1f ff\n"
+        );
+        let listing = Listing::new(&rom, Platform::Chip8);
+        assert_eq!(listing.lines.len(), 3);
+    }
+}
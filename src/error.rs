@@ -0,0 +1,48 @@
+use std::fmt;
+
+// everything that can stop the cpu from executing a rom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    // the instruction at pc is not one the interpreter understands
+    InvalidOpcode { pc: u16, opcode: u16 },
+    // call with every stack slot in use
+    StackOverflow { pc: u16 },
+    // return with nothing on the stack
+    StackUnderflow { pc: u16 },
+    // an instruction fetch, sprite read or register load/store went past the end of ram
+    MemoryOutOfBounds { addr: usize },
+    // the rom does not fit between 0x200 and the end of ram
+    RomTooLarge { size: usize, max: usize },
+}
+
+// what a successful step did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Executed,
+    // fx0a is blocked until a key is pressed, pc has not moved
+    WaitingForKey,
+    // dxyn is waiting for the next frame with the display wait quirk, pc has not moved
+    WaitingForVblank,
+    // the rom ran 00fd, pc stays on the exit instruction
+    Exit,
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid instruction {:04x} at {:#05x}", opcode, pc)
+            }
+            CpuError::StackOverflow { pc } => write!(f, "stack overflow at {:#05x}", pc),
+            CpuError::StackUnderflow { pc } => write!(f, "stack underflow at {:#05x}", pc),
+            CpuError::MemoryOutOfBounds { addr } => {
+                write!(f, "memory access out of bounds at {:#06x}", addr)
+            }
+            CpuError::RomTooLarge { size, max } => {
+                write!(f, "rom is {} bytes, at most {} bytes fit", size, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
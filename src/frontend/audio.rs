@@ -0,0 +1,175 @@
+// beeper tone played while the sound timer is running
+// an xo-chip audio pattern replaces the tone once the rom loads one
+
+use crate::cpu::AudioPattern;
+use std::str::FromStr;
+
+pub const DEFAULT_TONE_HZ: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    // value of the wave in -1.0 ..= 1.0 at a phase in 0.0 .. 1.0
+    pub fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        }
+    }
+}
+
+impl FromStr for Waveform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "square" => Ok(Waveform::Square),
+            "sine" => Ok(Waveform::Sine),
+            "triangle" => Ok(Waveform::Triangle),
+            _ => Err(format!(
+                "unknown waveform {}, expected square, sine or triangle",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    pub waveform: Waveform,
+    pub frequency: f32,
+    // 0.0 ..= 1.0
+    pub volume: f32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Square,
+            frequency: DEFAULT_TONE_HZ,
+            volume: DEFAULT_VOLUME,
+        }
+    }
+}
+
+// generates the tone one sample at a time
+pub struct Oscillator {
+    tone: Tone,
+    phase: f32,
+    // phase advanced per sample, tone frequency / sample rate
+    phase_inc: f32,
+    sample_rate: f32,
+    pattern: Option<AudioPattern>,
+    // position in the pattern, 0.0 .. 128.0
+    pattern_pos: f32,
+}
+
+impl Oscillator {
+    pub fn new(tone: Tone, sample_rate: u32) -> Self {
+        Self {
+            tone,
+            phase: 0.0,
+            phase_inc: tone.frequency / sample_rate as f32,
+            sample_rate: sample_rate as f32,
+            pattern: None,
+            pattern_pos: 0.0,
+        }
+    }
+
+    pub fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        if pattern.map(|p| p.bits) != self.pattern.map(|p| p.bits) {
+            self.pattern_pos = 0.0;
+        }
+        self.pattern = pattern;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        let volume = self.tone.volume.clamp(0.0, 1.0);
+        match self.pattern {
+            Some(pattern) => {
+                let step = pattern.rate() / self.sample_rate;
+                for x in out.iter_mut() {
+                    let lit = pattern.bit(self.pattern_pos as usize);
+                    *x = if lit { volume } else { -volume };
+                    self.pattern_pos = (self.pattern_pos + step) % 128.0;
+                }
+            }
+            None => {
+                for x in out.iter_mut() {
+                    *x = self.tone.waveform.sample(self.phase) * volume;
+                    self.phase = (self.phase + self.phase_inc) % 1.0;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_square() {
+        assert_eq!(Waveform::Square.sample(0.25), 1.0);
+        assert_eq!(Waveform::Square.sample(0.75), -1.0);
+    }
+
+    #[test]
+    fn test_sine() {
+        assert!((Waveform::Sine.sample(0.25) - 1.0).abs() < 1e-6);
+        assert!((Waveform::Sine.sample(0.75) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_triangle() {
+        assert_eq!(Waveform::Triangle.sample(0.0), -1.0);
+        assert_eq!(Waveform::Triangle.sample(0.25), 0.0);
+        assert_eq!(Waveform::Triangle.sample(0.5), 1.0);
+    }
+
+    #[test]
+    fn test_parse_waveform() {
+        assert_eq!("Sine".parse::<Waveform>(), Ok(Waveform::Sine));
+        assert!("saw".parse::<Waveform>().is_err());
+    }
+
+    #[test]
+    fn test_oscillator_applies_volume() {
+        let tone = Tone {
+            waveform: Waveform::Square,
+            frequency: 1.0,
+            volume: 0.5,
+        };
+        let mut oscillator = Oscillator::new(tone, 4);
+        let mut out = [0.0; 4];
+        oscillator.fill(&mut out);
+        assert_eq!(out, [0.5, 0.5, -0.5, -0.5]);
+        assert_eq!(oscillator.phase, 0.0);
+    }
+
+    #[test]
+    fn test_oscillator_plays_pattern() {
+        let mut oscillator = Oscillator::new(Tone::default(), 4000);
+        let mut bits = [0x0; 16];
+        bits[0] = 0b1010_0000;
+        oscillator.set_pattern(Some(AudioPattern { bits, pitch: 64 }));
+        let volume = DEFAULT_VOLUME;
+        let mut out = [0.0; 4];
+        oscillator.fill(&mut out);
+        // one bit per sample at the default pitch and a 4000 hz sample rate
+        assert_eq!(out, [volume, -volume, volume, -volume]);
+    }
+}
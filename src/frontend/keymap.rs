@@ -0,0 +1,173 @@
+// keyboard to keypad mapping
+// keys are looked up by their sdl key name, ignoring case
+//
+// the default layout maps the left hand side of a qwerty keyboard
+// onto the hex keypad
+//
+//  keyboard     keypad
+//  1 2 3 4      1 2 3 C
+//  Q W E R      4 5 6 D
+//  A S D F      7 8 9 E
+//  Z X C V      A 0 B F
+//
+// a layout file replaces the default, one binding per line written as
+// `<key name> = <keypad key>`, blank lines and lines starting with # are skipped
+//
+//  # azerty
+//  1 = 1
+//  a = 4
+//  q = 7
+//  w = A
+
+use std::collections::{BTreeMap, HashMap};
+
+const DEFAULT_LAYOUT: [(&str, u8); 16] = [
+    ("1", 0x1),
+    ("2", 0x2),
+    ("3", 0x3),
+    ("4", 0xc),
+    ("q", 0x4),
+    ("w", 0x5),
+    ("e", 0x6),
+    ("r", 0xd),
+    ("a", 0x7),
+    ("s", 0x8),
+    ("d", 0x9),
+    ("f", 0xe),
+    ("z", 0xa),
+    ("x", 0x0),
+    ("c", 0xb),
+    ("v", 0xf),
+];
+
+// rom database key hint and the keyboard key it is bound to
+const HINT_KEYS: &[(&str, &str)] = &[
+    ("up", "up"),
+    ("down", "down"),
+    ("left", "left"),
+    ("right", "right"),
+    ("a", "space"),
+    ("b", "left shift"),
+];
+
+pub struct Keymap {
+    keys: HashMap<String, u8>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let keys = DEFAULT_LAYOUT
+            .iter()
+            .map(|(name, key)| (name.to_string(), *key))
+            .collect();
+        Self { keys }
+    }
+}
+
+impl Keymap {
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("unable to read {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut keys = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, key) = line.rsplit_once('=').ok_or_else(|| {
+                format!("line {}: expected `<key name> = <keypad key>`", number + 1)
+            })?;
+            let name = name.trim();
+            let key = key.trim();
+            if name.is_empty() {
+                return Err(format!("line {}: missing key name", number + 1));
+            }
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(key) if key <= 0xf => key,
+                _ => {
+                    return Err(format!(
+                        "line {}: {} is not a keypad key 0 - F",
+                        number + 1,
+                        key
+                    ))
+                }
+            };
+            keys.insert(name.to_lowercase(), key);
+        }
+        Ok(Self { keys })
+    }
+
+    // bind one more keyboard key, replacing whatever it was bound to
+    pub fn bind(&mut self, name: &str, key: u8) {
+        self.keys.insert(name.to_lowercase(), key & 0x0f);
+    }
+
+    // bind the arrow keys, space and left shift to the keypad keys a rom
+    // database entry says the rom uses for up, down, left, right, a and b
+    pub fn bind_hints(&mut self, hints: &BTreeMap<String, u8>) {
+        for (hint, name) in HINT_KEYS {
+            if let Some(key) = hints.get(*hint) {
+                self.bind(name, *key);
+            }
+        }
+    }
+
+    // keypad key bound to the keyboard key with this name
+    pub fn key(&self, name: &str) -> Option<u8> {
+        self.keys.get(&name.to_lowercase()).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_layout() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.key("1"), Some(0x1));
+        assert_eq!(keymap.key("4"), Some(0xc));
+        assert_eq!(keymap.key("Q"), Some(0x4));
+        assert_eq!(keymap.key("x"), Some(0x0));
+        assert_eq!(keymap.key("V"), Some(0xf));
+        assert_eq!(keymap.key("5"), None);
+    }
+
+    #[test]
+    fn test_parse_replaces_default() {
+        let keymap = Keymap::parse("# azerty\n\na = 4\nQ = 7\n").unwrap();
+        assert_eq!(keymap.key("a"), Some(0x4));
+        assert_eq!(keymap.key("q"), Some(0x7));
+        assert_eq!(keymap.key("1"), None);
+    }
+
+    #[test]
+    fn test_parse_key_names_with_spaces() {
+        let keymap = Keymap::parse("Left Shift = f\nKeypad 0 = 0").unwrap();
+        assert_eq!(keymap.key("left shift"), Some(0xf));
+        assert_eq!(keymap.key("Keypad 0"), Some(0x0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Keymap::parse("a 4").is_err());
+        assert!(Keymap::parse("a = 10").is_err());
+        assert!(Keymap::parse("a = g").is_err());
+        assert!(Keymap::parse(" = 1").is_err());
+    }
+
+    #[test]
+    fn test_bind_hints() {
+        let mut keymap = Keymap::default();
+        let hints = BTreeMap::from([("up".to_string(), 0x5), ("a".to_string(), 0x6)]);
+        keymap.bind_hints(&hints);
+        assert_eq!(keymap.key("Up"), Some(0x5));
+        assert_eq!(keymap.key("Space"), Some(0x6));
+        assert_eq!(keymap.key("Down"), None);
+        assert_eq!(keymap.key("1"), Some(0x1));
+    }
+}
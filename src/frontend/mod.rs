@@ -0,0 +1,83 @@
+// frontend backends
+// the run loop only talks to these traits, so a new frontend is one more
+// implementation of them and never touches the cpu
+
+use crate::cpu::AudioPattern;
+use crate::{Cpu, CpuError};
+
+pub mod audio;
+pub mod keymap;
+pub mod null;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+
+// everything an input source can ask the run loop to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    // one of the 16 keypad keys went down or up
+    Key { key: u8, pressed: bool },
+    Quit,
+    // reset the cpu and load the rom again
+    Reset,
+    ToggleTurbo,
+    ToggleMute,
+    // save or load the numbered save state slot, 1 - 10
+    SaveState(u8),
+    LoadState(u8),
+    // the rewind key went down or up, the game runs backward while it is held
+    Rewind(bool),
+}
+
+// pixel colours, indexed by the plane bits of a pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [(u8, u8, u8); 4],
+}
+
+impl Default for Palette {
+    // black background, white for the first plane, greys for the xo-chip planes
+    fn default() -> Self {
+        Self {
+            colors: [
+                (0, 0, 0),
+                (255, 255, 255),
+                (0xaa, 0xaa, 0xaa),
+                (0x55, 0x55, 0x55),
+            ],
+        }
+    }
+}
+
+impl Palette {
+    // colours missing from the list keep their default
+    pub fn from_colors(colors: &[(u8, u8, u8)]) -> Self {
+        let mut palette = Self::default();
+        for (slot, color) in palette.colors.iter_mut().zip(colors) {
+            *slot = *color;
+        }
+        palette
+    }
+}
+
+pub trait VideoSink {
+    // draw the current framebuffer
+    fn present(&mut self, cpu: &Cpu) -> Result<(), String>;
+
+    // tell the user the cpu stopped, or that it is running again when None
+    fn show_error(&mut self, _error: Option<&CpuError>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub trait AudioSink {
+    // start or stop the beeper
+    fn set_beeping(&mut self, beeping: bool);
+
+    // play an xo-chip pattern instead of the tone, back to the tone when None
+    fn set_pattern(&mut self, _pattern: Option<AudioPattern>) {}
+}
+
+pub trait InputSource {
+    // every event that happened since the last poll
+    fn poll(&mut self) -> Vec<InputEvent>;
+}
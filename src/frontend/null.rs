@@ -0,0 +1,67 @@
+// headless backend
+// draws nothing and plays nothing, but remembers enough for tests to check
+
+use super::{AudioSink, InputEvent, InputSource, VideoSink};
+use crate::cpu::AudioPattern;
+use crate::{Cpu, CpuError};
+use std::collections::VecDeque;
+
+#[derive(Default)]
+pub struct NullVideo {
+    // number of frames presented
+    pub frames: usize,
+    pub error: Option<CpuError>,
+}
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _cpu: &Cpu) -> Result<(), String> {
+        self.frames += 1;
+        Ok(())
+    }
+
+    fn show_error(&mut self, error: Option<&CpuError>) -> Result<(), String> {
+        self.error = error.copied();
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct NullAudio {
+    pub beeping: bool,
+    pub pattern: Option<AudioPattern>,
+}
+
+impl AudioSink for NullAudio {
+    fn set_beeping(&mut self, beeping: bool) {
+        self.beeping = beeping;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.pattern = pattern;
+    }
+}
+
+// replays a script of events, one batch per poll
+// once the script runs out every poll is empty
+#[derive(Default)]
+pub struct NullInput {
+    script: VecDeque<Vec<InputEvent>>,
+}
+
+impl NullInput {
+    pub fn new(script: Vec<Vec<InputEvent>>) -> Self {
+        Self {
+            script: script.into(),
+        }
+    }
+
+    pub fn push(&mut self, events: Vec<InputEvent>) {
+        self.script.push_back(events);
+    }
+}
+
+impl InputSource for NullInput {
+    fn poll(&mut self) -> Vec<InputEvent> {
+        self.script.pop_front().unwrap_or_default()
+    }
+}
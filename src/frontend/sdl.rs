@@ -0,0 +1,257 @@
+// sdl2 backend
+//
+// hotkeys, checked before the keymap
+//  escape      quit
+//  backspace   reset
+//  tab         turbo on / off
+//  f12         mute on / off
+//  `           hold to rewind
+//  f1 - f10    load save state slot 1 - 10
+//  shift + f1 - f10
+//              save to slot 1 - 10
+
+use super::audio::{Oscillator, Tone};
+use super::keymap::Keymap;
+use super::{AudioSink, InputEvent, InputSource, Palette, VideoSink};
+use crate::cpu::AudioPattern;
+use crate::{Cpu, CpuError};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::{EventPump, Sdl};
+
+pub const WINDOW_TITLE: &str = "Chip-8 Rust Emulator";
+// the window keeps its size, pixels shrink in high resolution mode
+const WINDOW_WIDTH: u32 = 768;
+const WINDOW_HEIGHT: u32 = 384;
+const SAMPLE_RATE: i32 = 44_100;
+
+// open the window and audio device
+pub fn init(
+    keymap: Keymap,
+    tone: Tone,
+    palette: Palette,
+) -> Result<(SdlVideo, SdlAudio, SdlInput), String> {
+    let context = sdl2::init()?;
+    let video = SdlVideo::new(&context, palette)?;
+    let audio = SdlAudio::new(&context, tone)?;
+    let input = SdlInput {
+        event_pump: context.event_pump()?,
+        keymap,
+    };
+    Ok((video, audio, input))
+}
+
+pub struct SdlVideo {
+    canvas: Canvas<Window>,
+    palette: [Color; 4],
+    // keeps sdl alive for as long as the window is
+    _context: Sdl,
+}
+
+impl SdlVideo {
+    fn new(context: &Sdl, palette: Palette) -> Result<Self, String> {
+        let palette = palette.colors.map(|(r, g, b)| Color::RGB(r, g, b));
+        let video_subsystem = context.video()?;
+        let window = video_subsystem
+            .window(WINDOW_TITLE, WINDOW_WIDTH, WINDOW_HEIGHT)
+            .position_centered()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+        canvas.set_draw_color(palette[0]);
+        canvas.clear();
+        canvas.present();
+        Ok(Self {
+            canvas,
+            palette,
+            _context: context.clone(),
+        })
+    }
+}
+
+impl VideoSink for SdlVideo {
+    fn present(&mut self, cpu: &Cpu) -> Result<(), String> {
+        self.canvas.set_draw_color(self.palette[0]);
+        self.canvas.clear();
+        let scale = WINDOW_WIDTH / cpu.display_width() as u32;
+        for i in 0..cpu.display_width() {
+            for j in 0..cpu.display_height() {
+                let pixel = cpu.pixel(i, j) as usize & 0x3;
+                if pixel > 0 {
+                    self.canvas.set_draw_color(self.palette[pixel]);
+                    self.canvas.fill_rect(Rect::new(
+                        (i as u32 * scale) as i32,
+                        (j as u32 * scale) as i32,
+                        scale,
+                        scale,
+                    ))?;
+                }
+            }
+        }
+        self.canvas.present();
+        Ok(())
+    }
+
+    fn show_error(&mut self, error: Option<&CpuError>) -> Result<(), String> {
+        let title = match error {
+            Some(e) => format!("{} - {}", WINDOW_TITLE, e),
+            None => WINDOW_TITLE.to_string(),
+        };
+        self.canvas
+            .window_mut()
+            .set_title(&title)
+            .map_err(|e| e.to_string())
+    }
+}
+
+struct Beeper {
+    oscillator: Oscillator,
+}
+
+impl AudioCallback for Beeper {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.oscillator.fill(out);
+    }
+}
+
+pub struct SdlAudio {
+    device: AudioDevice<Beeper>,
+    // last pattern handed to the callback, saves locking the device every frame
+    pattern: Option<AudioPattern>,
+}
+
+impl SdlAudio {
+    fn new(context: &Sdl, tone: Tone) -> Result<Self, String> {
+        let audio_subsystem = context.audio()?;
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| Beeper {
+            oscillator: Oscillator::new(tone, spec.freq as u32),
+        })?;
+        Ok(Self {
+            device,
+            pattern: None,
+        })
+    }
+}
+
+impl AudioSink for SdlAudio {
+    fn set_beeping(&mut self, beeping: bool) {
+        if beeping {
+            self.device.resume();
+        } else {
+            self.device.pause();
+        }
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        if pattern != self.pattern {
+            self.device.lock().oscillator.set_pattern(pattern);
+            self.pattern = pattern;
+        }
+    }
+}
+
+pub struct SdlInput {
+    event_pump: EventPump,
+    keymap: Keymap,
+}
+
+impl InputSource for SdlInput {
+    fn poll(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        for event in self.event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => events.push(InputEvent::Quit),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => events.push(InputEvent::Reset),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => events.push(InputEvent::ToggleTurbo),
+                Event::KeyDown {
+                    keycode: Some(Keycode::F12),
+                    repeat: false,
+                    ..
+                } => events.push(InputEvent::ToggleMute),
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backquote),
+                    repeat: false,
+                    ..
+                } => events.push(InputEvent::Rewind(true)),
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backquote),
+                    ..
+                } => events.push(InputEvent::Rewind(false)),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    keymod,
+                    repeat,
+                    ..
+                } => {
+                    if let Some(slot) = save_slot(keycode) {
+                        if repeat {
+                            continue;
+                        }
+                        if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                            events.push(InputEvent::SaveState(slot));
+                        } else {
+                            events.push(InputEvent::LoadState(slot));
+                        }
+                    } else if let Some(key) = self.keymap.key(&keycode.name()) {
+                        events.push(InputEvent::Key { key, pressed: true });
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => {
+                    if let Some(key) = self.keymap.key(&keycode.name()) {
+                        events.push(InputEvent::Key {
+                            key,
+                            pressed: false,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+// save state slot of a function key
+fn save_slot(keycode: Keycode) -> Option<u8> {
+    let slot = match keycode {
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        Keycode::F5 => 5,
+        Keycode::F6 => 6,
+        Keycode::F7 => 7,
+        Keycode::F8 => 8,
+        Keycode::F9 => 9,
+        Keycode::F10 => 10,
+        _ => return None,
+    };
+    Some(slot)
+}
@@ -0,0 +1,743 @@
+// gdb remote serial protocol stub, so gdb (or anything else speaking the
+// protocol) can debug a rom over a local tcp socket
+// https://sourceware.org/gdb/current/onlinedocs/gdb.html/Remote-Protocol.html
+//
+// registers, in the order of the g packet and the target description
+//
+//  0 - 15   v0 - vf    8 bits
+//  16       i          16 bits, little endian like every multi byte value
+//  17       pc         16 bits
+//  18       sp, 19 dt, 20 st
+//
+// memory is ram from address 0, breakpoints (Z0, Z1) and watchpoints
+// (Z2 write, Z3 read, Z4 access) go to the debugger and the cpu, c and s
+// resume it, the runner reports the stop once the debugger pauses again
+
+use crate::cpu::Watchpoint;
+use crate::debugger::{Breakpoint, Debugger};
+use crate::Cpu;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+pub const SIGINT: u8 = 2;
+pub const SIGILL: u8 = 4;
+pub const SIGTRAP: u8 = 5;
+
+const REGISTERS: [(&str, usize, &str); 21] = [
+    ("v0", 8, "uint8"),
+    ("v1", 8, "uint8"),
+    ("v2", 8, "uint8"),
+    ("v3", 8, "uint8"),
+    ("v4", 8, "uint8"),
+    ("v5", 8, "uint8"),
+    ("v6", 8, "uint8"),
+    ("v7", 8, "uint8"),
+    ("v8", 8, "uint8"),
+    ("v9", 8, "uint8"),
+    ("va", 8, "uint8"),
+    ("vb", 8, "uint8"),
+    ("vc", 8, "uint8"),
+    ("vd", 8, "uint8"),
+    ("ve", 8, "uint8"),
+    ("vf", 8, "uint8"),
+    ("i", 16, "data_ptr"),
+    ("pc", 16, "code_ptr"),
+    ("sp", 8, "uint8"),
+    ("dt", 8, "uint8"),
+    ("st", 8, "uint8"),
+];
+
+// what the runner has to do after a poll
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    // gdb killed the target
+    Quit,
+    // gdb detached or went away, the rom runs on without a debugger
+    Detach,
+}
+
+pub struct Session {
+    stream: TcpStream,
+    // bytes received that do not make a whole packet yet
+    input: Vec<u8>,
+    // acks are dropped once gdb asks for QStartNoAckMode
+    ack: bool,
+    // a stop reply is owed for the last c or s
+    running: bool,
+}
+
+impl Session {
+    // blocks until gdb connects
+    pub fn listen(port: u16) -> Result<Self, String> {
+        let listener = TcpListener::bind(("127.0.0.1", port))
+            .map_err(|e| format!("unable to listen on port {}: {}", port, e))?;
+        println!("waiting for gdb on 127.0.0.1:{}....", port);
+        let (stream, peer) = listener
+            .accept()
+            .map_err(|e| format!("unable to accept gdb: {}", e))?;
+        println!("gdb connected from {}....", peer);
+        Self::new(stream)
+    }
+
+    pub fn new(stream: TcpStream) -> Result<Self, String> {
+        stream
+            .set_nonblocking(true)
+            .and_then(|()| stream.set_nodelay(true))
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            stream,
+            input: Vec::new(),
+            ack: true,
+            running: false,
+        })
+    }
+
+    // handle everything gdb sent since the last poll
+    pub fn poll(
+        &mut self,
+        debugger: &mut Debugger,
+        cpu: &mut Cpu,
+    ) -> Result<Option<Event>, String> {
+        let mut buffer = [0x0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Ok(Some(Event::Detach)),
+                Ok(n) => self.input.extend_from_slice(&buffer[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("gdb connection failed: {}", e)),
+            }
+        }
+        while let Some(frame) = self.next_frame() {
+            let event = match frame {
+                // gdb only waits for a stop reply after c or s
+                Frame::Interrupt => {
+                    if self.running {
+                        debugger.pause();
+                        self.stopped(SIGINT)?;
+                    }
+                    None
+                }
+                Frame::Corrupt => {
+                    self.write(b"-")?;
+                    None
+                }
+                Frame::Packet(packet) => {
+                    if self.ack {
+                        self.write(b"+")?;
+                    }
+                    self.packet(&packet, debugger, cpu)?
+                }
+            };
+            if event.is_some() {
+                return Ok(event);
+            }
+        }
+        Ok(None)
+    }
+
+    // the target stopped, gdb hears about it when it is waiting for that
+    pub fn stopped(&mut self, signal: u8) -> Result<(), String> {
+        if !self.running {
+            return Ok(());
+        }
+        self.running = false;
+        self.send(&format!("S{:02x}", signal))
+    }
+
+    // the rom ran 00fd
+    pub fn exited(&mut self) -> Result<(), String> {
+        self.running = false;
+        self.send("W00")
+    }
+
+    fn next_frame(&mut self) -> Option<Frame> {
+        loop {
+            match self.input.first()? {
+                0x03 => {
+                    self.input.remove(0);
+                    return Some(Frame::Interrupt);
+                }
+                b'$' => break,
+                // acks, and noise before a packet
+                _ => {
+                    self.input.remove(0);
+                }
+            }
+        }
+        let end = self.input.iter().position(|b| *b == b'#')?;
+        if self.input.len() < end + 3 {
+            return None;
+        }
+        let frame: Vec<u8> = self.input.drain(..end + 3).collect();
+        let body = &frame[1..end];
+        let checksum = std::str::from_utf8(&frame[end + 1..])
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if checksum != Some(checksum_of(body)) {
+            return Some(Frame::Corrupt);
+        }
+        Some(Frame::Packet(unescape(body)))
+    }
+
+    fn packet(
+        &mut self,
+        packet: &[u8],
+        debugger: &mut Debugger,
+        cpu: &mut Cpu,
+    ) -> Result<Option<Event>, String> {
+        // only X carries binary data, which is not supported
+        let packet = String::from_utf8_lossy(packet);
+        let reply = match reply(&packet, debugger, cpu) {
+            Reply::Packet(reply) => reply,
+            Reply::Resumed => {
+                self.running = true;
+                return Ok(None);
+            }
+            Reply::NoAck => {
+                self.send("OK")?;
+                self.ack = false;
+                return Ok(None);
+            }
+            Reply::Detach => {
+                self.send("OK")?;
+                return Ok(Some(Event::Detach));
+            }
+            Reply::Kill => return Ok(Some(Event::Quit)),
+        };
+        self.send(&reply)?;
+        Ok(None)
+    }
+
+    fn send(&mut self, reply: &str) -> Result<(), String> {
+        let body = escape(reply.as_bytes());
+        let mut packet = Vec::with_capacity(body.len() + 4);
+        packet.push(b'$');
+        packet.extend_from_slice(&body);
+        packet.extend_from_slice(format!("#{:02x}", checksum_of(&body)).as_bytes());
+        self.write(&packet)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut written = 0;
+        while written < bytes.len() {
+            match self.stream.write(&bytes[written..]) {
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::yield_now(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("gdb connection failed: {}", e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Frame {
+    Packet(Vec<u8>),
+    // ctrl-c, sent outside of a packet
+    Interrupt,
+    Corrupt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Reply {
+    Packet(String),
+    // the reply is the stop packet, sent once the debugger pauses
+    Resumed,
+    NoAck,
+    Detach,
+    Kill,
+}
+
+fn ok() -> Reply {
+    Reply::Packet("OK".to_string())
+}
+
+fn error() -> Reply {
+    Reply::Packet("E01".to_string())
+}
+
+// an empty packet tells gdb the request is not supported
+fn unsupported() -> Reply {
+    Reply::Packet(String::new())
+}
+
+fn reply(packet: &str, debugger: &mut Debugger, cpu: &mut Cpu) -> Reply {
+    let (command, args) = packet.split_at(packet.len().min(1));
+    match command {
+        "?" => Reply::Packet(format!("S{:02x}", SIGTRAP)),
+        "g" => Reply::Packet(
+            (0..REGISTERS.len())
+                .map(|n| read_register(cpu, n))
+                .collect(),
+        ),
+        "G" => write_registers(cpu, args),
+        "p" => match usize::from_str_radix(args, 16) {
+            Ok(n) if n < REGISTERS.len() => Reply::Packet(read_register(cpu, n)),
+            _ => error(),
+        },
+        "P" => match args.split_once('=') {
+            Some((n, value)) => match usize::from_str_radix(n, 16) {
+                Ok(n) if n < REGISTERS.len() => write_register(cpu, n, value),
+                _ => error(),
+            },
+            None => error(),
+        },
+        "m" => match parse_range(args) {
+            Some((addr, len)) => match cpu.ram().get(addr..) {
+                Some(rest) if !rest.is_empty() || len == 0 => {
+                    Reply::Packet(hex(&rest[..len.min(rest.len())]))
+                }
+                _ => error(),
+            },
+            None => error(),
+        },
+        "M" => write_memory(cpu, args),
+        "c" => resume(debugger, cpu, args, None),
+        "s" => resume(debugger, cpu, args, Some(1)),
+        "Z" | "z" => point(debugger, cpu, command == "Z", args),
+        "H" | "T" => ok(),
+        "D" => Reply::Detach,
+        "k" => Reply::Kill,
+        "v" => v_packet(debugger, cpu, packet),
+        "q" | "Q" => query(packet),
+        _ => unsupported(),
+    }
+}
+
+fn query(packet: &str) -> Reply {
+    if packet.starts_with("qSupported") {
+        return Reply::Packet(
+            "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;vContSupported+".to_string(),
+        );
+    }
+    if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+        return match parse_range(range) {
+            Some((offset, len)) => {
+                let xml = target_xml();
+                let chunk = xml.get(offset..).unwrap_or("");
+                let chunk = &chunk[..len.min(chunk.len())];
+                let more = offset + chunk.len() < xml.len();
+                Reply::Packet(format!("{}{}", if more { 'm' } else { 'l' }, chunk))
+            }
+            None => error(),
+        };
+    }
+    match packet {
+        "QStartNoAckMode" => Reply::NoAck,
+        "qAttached" => Reply::Packet("1".to_string()),
+        "qC" => Reply::Packet("QC1".to_string()),
+        "qfThreadInfo" => Reply::Packet("m1".to_string()),
+        "qsThreadInfo" => Reply::Packet("l".to_string()),
+        _ => unsupported(),
+    }
+}
+
+// only a single thread, so the first action of vCont is the one that counts
+fn v_packet(debugger: &mut Debugger, cpu: &mut Cpu, packet: &str) -> Reply {
+    if packet == "vCont?" {
+        return Reply::Packet("vCont;c;C;s;S".to_string());
+    }
+    let Some(actions) = packet.strip_prefix("vCont;") else {
+        // vMustReplyEmpty and anything else
+        return unsupported();
+    };
+    let action = actions.split(';').next().unwrap_or("");
+    let action = action.split(':').next().unwrap_or("");
+    match action.chars().next() {
+        Some('c' | 'C') => resume(debugger, cpu, "", None),
+        Some('s' | 'S') => resume(debugger, cpu, "", Some(1)),
+        _ => error(),
+    }
+}
+
+// c and s may give the address to resume from
+fn resume(debugger: &mut Debugger, cpu: &mut Cpu, addr: &str, step: Option<u32>) -> Reply {
+    if !addr.is_empty() {
+        match u16::from_str_radix(addr, 16) {
+            Ok(addr) => cpu.set_pc(addr),
+            Err(_) => return error(),
+        }
+    }
+    match step {
+        Some(count) => debugger.step(count, cpu),
+        None => debugger.run(cpu),
+    }
+    Reply::Resumed
+}
+
+// Z<type>,<addr>,<kind> inserts, z removes, kind is the length for watchpoints
+fn point(debugger: &mut Debugger, cpu: &mut Cpu, insert: bool, args: &str) -> Reply {
+    let mut fields = args.split(',');
+    let (Some(kind), Some(addr), Some(len)) = (fields.next(), fields.next(), fields.next()) else {
+        return error();
+    };
+    let (Ok(addr), Ok(len)) = (u16::from_str_radix(addr, 16), u16::from_str_radix(len, 16)) else {
+        return error();
+    };
+    let (read, write) = match kind {
+        "0" | "1" => {
+            if insert {
+                debugger.add_breakpoint(Breakpoint {
+                    addr,
+                    condition: None,
+                });
+            } else {
+                debugger.remove_breakpoints(addr);
+            }
+            return ok();
+        }
+        "2" => (false, true),
+        "3" => (true, false),
+        "4" => (true, true),
+        _ => return unsupported(),
+    };
+    if insert {
+        cpu.add_watchpoint(Watchpoint {
+            start: addr,
+            end: addr.saturating_add(len.max(1) - 1),
+            read,
+            write,
+            exec: false,
+        });
+    } else {
+        // every watchpoint starting there goes, gdb removes them one at a time anyway
+        cpu.remove_watchpoints(addr);
+    }
+    ok()
+}
+
+fn read_register(cpu: &Cpu, n: usize) -> String {
+    match n {
+        0..=15 => hex(&[cpu.registers()[n]]),
+        16 => hex(&cpu.i().to_le_bytes()),
+        17 => hex(&cpu.pc().to_le_bytes()),
+        18 => hex(&[cpu.sp()]),
+        19 => hex(&[cpu.delay_timer()]),
+        _ => hex(&[cpu.sound_timer()]),
+    }
+}
+
+fn write_register(cpu: &mut Cpu, n: usize, value: &str) -> Reply {
+    let Some(bytes) = unhex(value) else {
+        return error();
+    };
+    let size = REGISTERS[n].1 / 8;
+    if bytes.len() != size {
+        return error();
+    }
+    let value = if size == 2 {
+        u16::from_le_bytes([bytes[0], bytes[1]])
+    } else {
+        bytes[0] as u16
+    };
+    match n {
+        0..=15 => cpu.set_register(n, value as u8),
+        16 => cpu.set_i(value),
+        17 => cpu.set_pc(value),
+        // the stack pointer only moves with calls and returns
+        18 if value == cpu.sp() as u16 => {}
+        18 => return error(),
+        19 => cpu.set_delay_timer(value as u8),
+        _ => cpu.set_sound_timer(value as u8),
+    }
+    ok()
+}
+
+fn write_registers(cpu: &mut Cpu, values: &str) -> Reply {
+    let mut rest = values;
+    for (n, (_, bits, _)) in REGISTERS.iter().enumerate() {
+        let digits = bits / 4;
+        if rest.len() < digits {
+            return error();
+        }
+        let (value, tail) = rest.split_at(digits);
+        if let Reply::Packet(reply) = write_register(cpu, n, value) {
+            if reply != "OK" {
+                return error();
+            }
+        }
+        rest = tail;
+    }
+    ok()
+}
+
+// M<addr>,<len>:<hex bytes>
+fn write_memory(cpu: &mut Cpu, args: &str) -> Reply {
+    let Some((range, data)) = args.split_once(':') else {
+        return error();
+    };
+    let (Some((addr, len)), Some(bytes)) = (parse_range(range), unhex(data)) else {
+        return error();
+    };
+    if bytes.len() != len {
+        return error();
+    }
+    let Some(end) = addr.checked_add(len) else {
+        return error();
+    };
+    match cpu.ram_mut().get_mut(addr..end) {
+        Some(ram) => {
+            ram.copy_from_slice(&bytes);
+            ok()
+        }
+        None => error(),
+    }
+}
+
+pub fn target_xml() -> String {
+    let registers: String = REGISTERS
+        .iter()
+        .enumerate()
+        .map(|(n, (name, bits, kind))| {
+            format!(
+                "    <reg name=\"{}\" bitsize=\"{}\" type=\"{}\" regnum=\"{}\"/>\n",
+                name, bits, kind, n
+            )
+        })
+        .collect();
+    format!(
+        "<?xml version=\"1.0\"?>\n\
+         <!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n\
+         <target version=\"1.0\">\n  \
+         <feature name=\"org.chip8.core\">\n\
+         {}  </feature>\n\
+         </target>\n",
+        registers
+    )
+}
+
+// <addr>,<len> in hex
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((
+        usize::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|n| u8::from_str_radix(text.get(n..n + 2)?, 16).ok())
+        .collect()
+}
+
+fn checksum_of(body: &[u8]) -> u8 {
+    body.iter().fold(0x0, |sum, b| sum.wrapping_add(*b))
+}
+
+// $, #, } and * are sent as } followed by the byte xor 0x20
+fn escape(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for b in bytes {
+        if matches!(b, b'$' | b'#' | b'}' | b'*') {
+            out.extend_from_slice(&[b'}', b ^ 0x20]);
+        } else {
+            out.push(*b);
+        }
+    }
+    out
+}
+
+fn unescape(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'}' => out.extend(bytes.next().map(|b| b ^ 0x20)),
+            _ => out.push(*b),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu() -> Cpu {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(
+            [
+                // address 0x200
+                // v3 = 0x42
+                0x63, 0x42, // address 0x202
+                // i = 0x300
+                0xa3, 0x00, // address 0x204
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+        )
+        .unwrap();
+        cpu
+    }
+
+    fn packet(debugger: &mut Debugger, cpu: &mut Cpu, packet: &str) -> String {
+        match reply(packet, debugger, cpu) {
+            Reply::Packet(reply) => reply,
+            other => panic!("{} replied {:?}", packet, other),
+        }
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        let registers = packet(&mut debugger, &mut cpu, "g");
+        assert_eq!(registers.len(), (16 + 2 + 2 + 3) * 2);
+        assert_eq!(&registers[6..8], "42");
+        // i and pc are little endian
+        assert_eq!(&registers[32..40], "00030402");
+        assert_eq!(packet(&mut debugger, &mut cpu, "p11"), "0402");
+
+        assert_eq!(packet(&mut debugger, &mut cpu, "P3=17"), "OK");
+        assert_eq!(cpu.registers()[3], 0x17);
+        assert_eq!(packet(&mut debugger, &mut cpu, "P11=0002"), "OK");
+        assert_eq!(cpu.pc(), 0x200);
+        assert_eq!(packet(&mut debugger, &mut cpu, "P13=3c"), "OK");
+        assert_eq!(cpu.delay_timer(), 0x3c);
+        assert_eq!(packet(&mut debugger, &mut cpu, "P12=05"), "E01");
+        assert_eq!(packet(&mut debugger, &mut cpu, "p15"), "E01");
+
+        let mut written = registers.clone();
+        written.replace_range(0..2, "aa");
+        assert_eq!(
+            packet(&mut debugger, &mut cpu, &format!("G{}", written)),
+            "OK"
+        );
+        assert_eq!(cpu.registers()[0], 0xaa);
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn test_memory() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu();
+        assert_eq!(packet(&mut debugger, &mut cpu, "m200,6"), "6342a3001200");
+        // reads stop at the end of ram
+        assert_eq!(packet(&mut debugger, &mut cpu, "mffe,4"), "0000");
+        assert_eq!(packet(&mut debugger, &mut cpu, "m1000,1"), "E01");
+        assert_eq!(packet(&mut debugger, &mut cpu, "M300,2:beef"), "OK");
+        assert_eq!(&cpu.ram()[0x300..0x302], [0xbe, 0xef]);
+        assert_eq!(packet(&mut debugger, &mut cpu, "Mfff,2:beef"), "E01");
+        assert_eq!(
+            packet(&mut debugger, &mut cpu, "Mffffffffffffffff,2:beef"),
+            "E01"
+        );
+    }
+
+    #[test]
+    fn test_breakpoints_and_resume() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu();
+        assert_eq!(packet(&mut debugger, &mut cpu, "Z0,204,2"), "OK");
+        assert_eq!(debugger.breakpoints().len(), 1);
+        assert_eq!(reply("c", &mut debugger, &mut cpu), Reply::Resumed);
+        assert!(!debugger.paused());
+        while !debugger.check(&cpu) {
+            cpu.step().unwrap();
+            debugger.executed(&mut cpu);
+        }
+        assert_eq!(cpu.pc(), 0x204);
+
+        assert_eq!(reply("vCont;s:1", &mut debugger, &mut cpu), Reply::Resumed);
+        assert!(!debugger.check(&cpu));
+        cpu.step().unwrap();
+        debugger.executed(&mut cpu);
+        assert!(debugger.check(&cpu));
+        assert_eq!(cpu.pc(), 0x200);
+
+        assert_eq!(packet(&mut debugger, &mut cpu, "z0,204,2"), "OK");
+        assert!(debugger.breakpoints().is_empty());
+
+        assert_eq!(packet(&mut debugger, &mut cpu, "Z2,300,2"), "OK");
+        assert_eq!(cpu.watchpoints()[0].end, 0x301);
+        assert!(cpu.watchpoints()[0].write && !cpu.watchpoints()[0].read);
+        assert_eq!(packet(&mut debugger, &mut cpu, "z2,300,2"), "OK");
+        assert!(cpu.watchpoints().is_empty());
+    }
+
+    #[test]
+    fn test_target_description() {
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu();
+        let xml = target_xml();
+        assert!(xml.contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\" regnum=\"17\"/>"));
+        let first = packet(
+            &mut debugger,
+            &mut cpu,
+            "qXfer:features:read:target.xml:0,10",
+        );
+        assert_eq!(first, format!("m{}", &xml[..0x10]));
+        let rest = packet(
+            &mut debugger,
+            &mut cpu,
+            "qXfer:features:read:target.xml:10,1000",
+        );
+        assert_eq!(rest, format!("l{}", &xml[0x10..]));
+        assert!(packet(&mut debugger, &mut cpu, "qSupported:multiprocess+").contains("qXfer"));
+        assert_eq!(packet(&mut debugger, &mut cpu, "vMustReplyEmpty"), "");
+    }
+
+    #[test]
+    fn test_framing() {
+        assert_eq!(escape(b"a#b}"), b"a}\x03b}]");
+        assert_eq!(unescape(b"a}\x03b}]"), b"a#b}");
+        assert_eq!(checksum_of(b"OK"), 0x9a);
+    }
+
+    // a scripted client over a real socket
+    #[test]
+    fn test_session() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut session = Session::new(listener.accept().unwrap().0).unwrap();
+        let mut debugger = Debugger::new();
+        let mut cpu = cpu();
+
+        let mut exchange = |session: &mut Session, sent: &[u8], expected: &[u8]| {
+            client.write_all(sent).unwrap();
+            let mut received = Vec::new();
+            while received.len() < expected.len() {
+                session.poll(&mut debugger, &mut cpu).unwrap();
+                let mut buffer = [0x0; 256];
+                client
+                    .set_read_timeout(Some(std::time::Duration::from_millis(10)))
+                    .unwrap();
+                if let Ok(n) = client.read(&mut buffer) {
+                    received.extend_from_slice(&buffer[..n]);
+                }
+            }
+            assert_eq!(received, expected);
+        };
+        exchange(&mut session, b"+$?#3f", b"+$S05#b8");
+        exchange(&mut session, b"$m200,2#5d", b"+$6342#cf");
+        // a bad checksum asks gdb to send again
+        exchange(&mut session, b"$m200,2#00", b"-");
+        exchange(&mut session, b"$QStartNoAckMode#b0", b"+$OK#9a");
+        // an interrupt while stopped is dropped, no stop reply is owed
+        exchange(&mut session, b"\x03$p3#a3", b"$00#60");
+        exchange(&mut session, b"$c#63\x03", b"$S02#b5");
+        session.exited().unwrap();
+        let mut buffer = [0x0; 7];
+        client.read_exact(&mut buffer).unwrap();
+        assert_eq!(&buffer, b"$W00#b7");
+
+        drop(client);
+        assert_eq!(
+            session.poll(&mut debugger, &mut cpu),
+            Ok(Some(Event::Detach))
+        );
+    }
+}
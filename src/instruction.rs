@@ -0,0 +1,352 @@
+// decoded instructions, one variant per opcode with its operands pulled out
+// http://devernay.free.fr/hacks/chip8/C8TECH10.HTM
+//
+// decode accepts every opcode of every platform, supported_on says whether
+// a platform has it
+
+use crate::platform::Platform;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // 0nnn, a machine language routine on the cosmac vip
+    Sys(u16),
+    // 00e0
+    Cls,
+    // 00ee
+    Ret,
+    // 00cn
+    ScrollDown(u8),
+    // 00dn
+    ScrollUp(u8),
+    // 00fb
+    ScrollRight,
+    // 00fc
+    ScrollLeft,
+    // 00fd
+    Exit,
+    // 00fe
+    Low,
+    // 00ff
+    High,
+    // 1nnn
+    Jump(u16),
+    // 2nnn
+    Call(u16),
+    // 3xkk
+    SkipEqImm { x: usize, kk: u8 },
+    // 4xkk
+    SkipNeImm { x: usize, kk: u8 },
+    // 5xy0
+    SkipEq { x: usize, y: usize },
+    // 5xy2
+    StoreRange { x: usize, y: usize },
+    // 5xy3
+    LoadRange { x: usize, y: usize },
+    // 6xkk
+    LoadImm { x: usize, kk: u8 },
+    // 7xkk
+    AddImm { x: usize, kk: u8 },
+    // 8xy0
+    Move { x: usize, y: usize },
+    // 8xy1
+    Or { x: usize, y: usize },
+    // 8xy2
+    And { x: usize, y: usize },
+    // 8xy3
+    Xor { x: usize, y: usize },
+    // 8xy4
+    Add { x: usize, y: usize },
+    // 8xy5
+    Sub { x: usize, y: usize },
+    // 8xy6
+    ShiftRight { x: usize, y: usize },
+    // 8xy7
+    SubN { x: usize, y: usize },
+    // 8xye
+    ShiftLeft { x: usize, y: usize },
+    // 9xy0
+    SkipNe { x: usize, y: usize },
+    // annn
+    LoadI(u16),
+    // bnnn
+    JumpV0(u16),
+    // cxkk
+    Random { x: usize, kk: u8 },
+    // dxyn
+    Draw { x: usize, y: usize, n: u8 },
+    // ex9e
+    SkipKey(usize),
+    // exa1
+    SkipNotKey(usize),
+    // f000 nnnn, the address is the word after the opcode
+    LoadILong,
+    // fn01
+    Plane(u8),
+    // f002
+    Audio,
+    // fx07
+    LoadDelay(usize),
+    // fx0a
+    WaitKey(usize),
+    // fx15
+    SetDelay(usize),
+    // fx18
+    SetSound(usize),
+    // fx1e
+    AddI(usize),
+    // fx29
+    Font(usize),
+    // fx30
+    BigFont(usize),
+    // fx33
+    Bcd(usize),
+    // fx3a
+    Pitch(usize),
+    // fx55
+    Store(usize),
+    // fx65
+    Load(usize),
+    // fx75
+    SaveFlags(usize),
+    // fx85
+    LoadFlags(usize),
+}
+
+// the opcode back when it is not an instruction on any platform
+pub fn decode(opcode: u16) -> Result<Instruction, u16> {
+    use Instruction::*;
+    let x = ((opcode & 0x0f00) >> 8) as usize;
+    let y = ((opcode & 0x00f0) >> 4) as usize;
+    let n = (opcode & 0x000f) as u8;
+    let kk = (opcode & 0x00ff) as u8;
+    let nnn = opcode & 0x0fff;
+    let instruction = match opcode & 0xf000 {
+        0x0000 => match opcode {
+            0x00e0 => Cls,
+            0x00ee => Ret,
+            0x00c0..=0x00cf => ScrollDown(n),
+            0x00d0..=0x00df => ScrollUp(n),
+            0x00fb => ScrollRight,
+            0x00fc => ScrollLeft,
+            0x00fd => Exit,
+            0x00fe => Low,
+            0x00ff => High,
+            _ => Sys(nnn),
+        },
+        0x1000 => Jump(nnn),
+        0x2000 => Call(nnn),
+        0x3000 => SkipEqImm { x, kk },
+        0x4000 => SkipNeImm { x, kk },
+        0x5000 => match n {
+            0x0 => SkipEq { x, y },
+            0x2 => StoreRange { x, y },
+            0x3 => LoadRange { x, y },
+            _ => return Err(opcode),
+        },
+        0x6000 => LoadImm { x, kk },
+        0x7000 => AddImm { x, kk },
+        0x8000 => match n {
+            0x0 => Move { x, y },
+            0x1 => Or { x, y },
+            0x2 => And { x, y },
+            0x3 => Xor { x, y },
+            0x4 => Add { x, y },
+            0x5 => Sub { x, y },
+            0x6 => ShiftRight { x, y },
+            0x7 => SubN { x, y },
+            0xe => ShiftLeft { x, y },
+            _ => return Err(opcode),
+        },
+        0x9000 if n == 0 => SkipNe { x, y },
+        0xa000 => LoadI(nnn),
+        0xb000 => JumpV0(nnn),
+        0xc000 => Random { x, kk },
+        0xd000 => Draw { x, y, n },
+        0xe000 => match kk {
+            0x9e => SkipKey(x),
+            0xa1 => SkipNotKey(x),
+            _ => return Err(opcode),
+        },
+        0xf000 => match kk {
+            0x00 if x == 0 => LoadILong,
+            0x01 => Plane(x as u8),
+            0x02 if x == 0 => Audio,
+            0x07 => LoadDelay(x),
+            0x0a => WaitKey(x),
+            0x15 => SetDelay(x),
+            0x18 => SetSound(x),
+            0x1e => AddI(x),
+            0x29 => Font(x),
+            0x30 => BigFont(x),
+            0x33 => Bcd(x),
+            0x3a => Pitch(x),
+            0x55 => Store(x),
+            0x65 => Load(x),
+            0x75 => SaveFlags(x),
+            0x85 => LoadFlags(x),
+            _ => return Err(opcode),
+        },
+        _ => return Err(opcode),
+    };
+    Ok(instruction)
+}
+
+// like decode, but also turns away the instructions the platform lacks
+pub fn decode_for(platform: Platform, opcode: u16) -> Result<Instruction, u16> {
+    match decode(opcode)? {
+        instruction if instruction.supported_on(platform) => Ok(instruction),
+        _ => Err(opcode),
+    }
+}
+
+impl Instruction {
+    pub fn supported_on(&self, platform: Platform) -> bool {
+        use Instruction::*;
+        let schip = platform.supports_superchip();
+        let schip11 = platform.supports_superchip_1_1();
+        let xochip = platform.supports_xochip();
+        match *self {
+            // only the cosmac vip could run machine code, everything since ignores it
+            Sys(_) => false,
+            Exit | Low | High => schip,
+            ScrollDown(_) | ScrollRight | ScrollLeft | BigFont(_) => schip11,
+            ScrollUp(_)
+            | StoreRange { .. }
+            | LoadRange { .. }
+            | LoadILong
+            | Plane(_)
+            | Audio
+            | Pitch(_) => xochip,
+            // super-chip only has 8 rpl flags, xo-chip has 16
+            SaveFlags(x) | LoadFlags(x) => xochip || schip && x < 8,
+            _ => true,
+        }
+    }
+
+    // the opcode the instruction decodes from, f000 without its address
+    pub fn encode(&self) -> u16 {
+        use Instruction::*;
+        let xy = |base: u16, x: usize, y: usize| base | (x as u16) << 8 | (y as u16) << 4;
+        let xkk = |base: u16, x: usize, kk: u8| base | (x as u16) << 8 | kk as u16;
+        let fx = |kk: u16, x: usize| 0xf000 | (x as u16) << 8 | kk;
+        match *self {
+            Sys(nnn) => nnn,
+            Cls => 0x00e0,
+            Ret => 0x00ee,
+            ScrollDown(n) => 0x00c0 | n as u16,
+            ScrollUp(n) => 0x00d0 | n as u16,
+            ScrollRight => 0x00fb,
+            ScrollLeft => 0x00fc,
+            Exit => 0x00fd,
+            Low => 0x00fe,
+            High => 0x00ff,
+            Jump(nnn) => 0x1000 | nnn,
+            Call(nnn) => 0x2000 | nnn,
+            SkipEqImm { x, kk } => xkk(0x3000, x, kk),
+            SkipNeImm { x, kk } => xkk(0x4000, x, kk),
+            SkipEq { x, y } => xy(0x5000, x, y),
+            StoreRange { x, y } => xy(0x5002, x, y),
+            LoadRange { x, y } => xy(0x5003, x, y),
+            LoadImm { x, kk } => xkk(0x6000, x, kk),
+            AddImm { x, kk } => xkk(0x7000, x, kk),
+            Move { x, y } => xy(0x8000, x, y),
+            Or { x, y } => xy(0x8001, x, y),
+            And { x, y } => xy(0x8002, x, y),
+            Xor { x, y } => xy(0x8003, x, y),
+            Add { x, y } => xy(0x8004, x, y),
+            Sub { x, y } => xy(0x8005, x, y),
+            ShiftRight { x, y } => xy(0x8006, x, y),
+            SubN { x, y } => xy(0x8007, x, y),
+            ShiftLeft { x, y } => xy(0x800e, x, y),
+            SkipNe { x, y } => xy(0x9000, x, y),
+            LoadI(nnn) => 0xa000 | nnn,
+            JumpV0(nnn) => 0xb000 | nnn,
+            Random { x, kk } => xkk(0xc000, x, kk),
+            Draw { x, y, n } => xy(0xd000, x, y) | n as u16,
+            SkipKey(x) => xkk(0xe000, x, 0x9e),
+            SkipNotKey(x) => xkk(0xe000, x, 0xa1),
+            LoadILong => 0xf000,
+            Plane(n) => fx(0x01, n as usize),
+            Audio => 0xf002,
+            LoadDelay(x) => fx(0x07, x),
+            WaitKey(x) => fx(0x0a, x),
+            SetDelay(x) => fx(0x15, x),
+            SetSound(x) => fx(0x18, x),
+            AddI(x) => fx(0x1e, x),
+            Font(x) => fx(0x29, x),
+            BigFont(x) => fx(0x30, x),
+            Bcd(x) => fx(0x33, x),
+            Pitch(x) => fx(0x3a, x),
+            Store(x) => fx(0x55, x),
+            Load(x) => fx(0x65, x),
+            SaveFlags(x) => fx(0x75, x),
+            LoadFlags(x) => fx(0x85, x),
+        }
+    }
+
+    // bytes the instruction takes up in memory
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LoadILong => 4,
+            _ => 2,
+        }
+    }
+
+    // where a jump or call goes, not known for jp v0
+    pub fn target(&self) -> Option<u16> {
+        match *self {
+            Instruction::Jump(nnn) | Instruction::Call(nnn) | Instruction::JumpV0(nnn) => Some(nnn),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(0x00e0), Ok(Instruction::Cls));
+        assert_eq!(decode(0x1234), Ok(Instruction::Jump(0x234)));
+        assert_eq!(
+            decode(0x8ab6),
+            Ok(Instruction::ShiftRight { x: 0xa, y: 0xb })
+        );
+        assert_eq!(decode(0xd125), Ok(Instruction::Draw { x: 1, y: 2, n: 5 }));
+        assert_eq!(decode(0xf000), Ok(Instruction::LoadILong));
+        assert_eq!(decode(0x5121), Err(0x5121));
+        assert_eq!(decode(0xf100), Err(0xf100));
+        assert_eq!(decode(0xe0ff), Err(0xe0ff));
+    }
+
+    #[test]
+    fn test_encode_round_trips() {
+        for opcode in 0..=0xffff {
+            if let Ok(instruction) = decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{:?}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_for() {
+        assert_eq!(decode_for(Platform::Chip8, 0x00ff), Err(0x00ff));
+        assert_eq!(
+            decode_for(Platform::SuperChip10, 0x00ff),
+            Ok(Instruction::High)
+        );
+        assert_eq!(decode_for(Platform::SuperChip10, 0x00c1), Err(0x00c1));
+        assert_eq!(
+            decode_for(Platform::SuperChip, 0x00c1),
+            Ok(Instruction::ScrollDown(1))
+        );
+        assert_eq!(decode_for(Platform::SuperChip, 0xf875), Err(0xf875));
+        assert_eq!(
+            decode_for(Platform::XoChip, 0xf875),
+            Ok(Instruction::SaveFlags(8))
+        );
+        assert_eq!(decode_for(Platform::SuperChip, 0xf000), Err(0xf000));
+        assert_eq!(decode_for(Platform::Chip8, 0x0123), Err(0x0123));
+    }
+}
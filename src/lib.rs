@@ -0,0 +1,29 @@
+// chip-8 interpreter core
+// everything needed to run a rom headless, the sdl2 backend is behind the sdl feature
+
+pub mod asm;
+mod bytes;
+pub mod cpu;
+pub mod dap;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod font;
+pub mod frontend;
+pub mod gdb;
+pub mod instruction;
+pub mod movie;
+pub mod platform;
+pub mod quirks;
+pub mod reload;
+pub mod rewind;
+pub mod rng;
+pub mod romdb;
+pub mod runner;
+pub mod scheduler;
+
+pub use cpu::Cpu;
+pub use error::{CpuError, StepOutcome};
+pub use platform::Platform;
+pub use quirks::Quirks;
+pub use rng::RngMode;
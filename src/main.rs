@@ -1,90 +1,446 @@
-use clap::Parser;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+use clap::{Parser, Subcommand};
 
-mod cpu;
+use chip8::asm::assemble;
+use chip8::dap;
+use chip8::debugger::{self, Debugger};
+use chip8::disasm::{Listing, Syntax};
+use chip8::frontend::audio::{self, Tone, Waveform};
+use chip8::frontend::keymap::Keymap;
+use chip8::frontend::{sdl, Palette};
+use chip8::gdb;
+use chip8::movie::Movie;
+use chip8::reload::{Build, Watcher};
+use chip8::rewind::{self, Rewind};
+use chip8::romdb::RomDatabase;
+use chip8::runner::Runner;
+use chip8::{scheduler, Cpu, Platform, RngMode};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[command(subcommand_negates_reqs = true)]
 struct Args {
-    #[arg(short, long)]
-    rom_path: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    // only optional when a subcommand is given
+    /// Rom to run, files ending in .8o are octo source, assembled for
+    /// --platform
+    #[arg(short, long, required = true)]
+    rom_path: Option<String>,
+
+    /// Interpreter the rom was written for, picks the instruction set and quirks:
+    /// chip8, vip, chip48, schip1.0, schip1.1 (or schip) or xochip.
+    /// Overrides the rom database, chip8 when neither says otherwise
+    #[arg(long)]
+    platform: Option<Platform>,
+
+    /// Rom database to use instead of the bundled one, a programs.json from
+    /// the community chip-8-database
+    #[arg(long, conflicts_with = "no_rom_db")]
+    rom_db: Option<String>,
+
+    /// Ignore the rom database
+    #[arg(long)]
+    no_rom_db: bool,
+
+    /// Print what the rom database knows about the rom and exit
+    #[arg(long)]
+    rom_info: bool,
+
+    /// Seed for the random numbers of cxkk, the same seed gives the same run
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Random number generator, wyrand or vip for the routine of the
+    /// cosmac vip interpreter
+    #[arg(long, default_value = "wyrand")]
+    rng: RngMode,
+
+    /// Address of the 5 byte hex font, inside the interpreter area
+    #[arg(long, value_parser = parse_address)]
+    font_address: Option<u16>,
+
+    /// Address of the 10 byte super-chip font, inside the interpreter area
+    #[arg(long, value_parser = parse_address)]
+    big_font_address: Option<u16>,
+
+    /// File holding a replacement for the 80 byte hex font
+    #[arg(long)]
+    font: Option<String>,
+
+    /// File holding a replacement for the 160 byte super-chip font
+    #[arg(long)]
+    big_font: Option<String>,
+
+    /// Keyboard layout file, see frontend/keymap.rs for the format
+    #[arg(long)]
+    keymap: Option<String>,
+
+    /// Instructions executed per 60 Hz frame, overrides the rom database
+    #[arg(long, conflicts_with = "clock_hz")]
+    ipf: Option<u32>,
+
+    /// Instructions executed per second, rounded to whole frames
+    #[arg(long)]
+    clock_hz: Option<u32>,
+
+    /// Frequency of the beeper in Hz
+    #[arg(long, default_value_t = audio::DEFAULT_TONE_HZ)]
+    tone_hz: f32,
+
+    /// Volume of the beeper, from 0.0 to 1.0
+    #[arg(long, default_value_t = audio::DEFAULT_VOLUME)]
+    volume: f32,
+
+    /// Seconds of play kept for rewinding with the ` key, 0 turns rewinding off
+    #[arg(long, default_value_t = rewind::DEFAULT_SECONDS)]
+    rewind_seconds: u32,
+
+    /// Memory the rewind buffer may use, in MiB
+    #[arg(long, default_value_t = rewind::DEFAULT_BUDGET / (1024 * 1024))]
+    rewind_budget: usize,
+
+    /// Record the keypad to a movie file, written when the emulator quits
+    #[arg(long, conflicts_with = "play")]
+    record: Option<String>,
+
+    /// Play a movie file back and check the run ends where the recording did
+    #[arg(long)]
+    play: Option<String>,
+
+    /// Start paused in the debugger, commands are typed at the terminal,
+    /// help lists them
+    #[arg(long)]
+    debug: bool,
+
+    /// Wait for gdb to connect on this local port and let it drive the
+    /// debugger, registers are v0 - vf, i, pc, sp, dt and st
+    #[arg(long, conflicts_with_all = ["debug", "play", "record"])]
+    gdb: Option<u16>,
+
+    /// Reload the rom whenever its file changes, .8o source is assembled again
+    #[arg(long)]
+    watch: bool,
+
+    /// When reloading, go back to the last save state made or loaded if the
+    /// rom kept its length and labels
+    #[arg(long, requires = "watch")]
+    watch_restore: bool,
+
+    /// Shape of the beeper tone
+    #[arg(long, default_value = "square")]
+    waveform: Waveform,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a listing of a rom with addresses, bytes and labels on the
+    /// targets of jumps and calls
+    Disasm {
+        rom: String,
+
+        /// Platform whose instructions are decoded, the rom database or
+        /// chip8 when not given
+        #[arg(long)]
+        platform: Option<Platform>,
+
+        /// Mnemonics to print, cowgod or octo
+        #[arg(long, default_value = "cowgod")]
+        syntax: Syntax,
+    },
+    /// Assemble octo source into a rom
+    Asm {
+        source: String,
+
+        /// Rom to write, the source with a .ch8 extension when not given
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Platform whose instructions may be used, chip8 when not given
+        #[arg(long, default_value = "chip8")]
+        platform: Platform,
+    },
+    /// Serve the debug adapter protocol on stdin and stdout, the rom comes
+    /// with the launch request
+    Dap,
+}
+
+fn disasm(rom_path: &str, platform: Option<Platform>, syntax: Syntax) -> Result<(), String> {
+    let rom = std::fs::read(rom_path).map_err(|e| format!("unable to read {}: {}", rom_path, e))?;
+    let platform = platform
+        .or_else(|| {
+            RomDatabase::bundled()
+                .lookup(&rom)
+                .and_then(|info| info.platform)
+        })
+        .unwrap_or_default();
+    print!("{}", Listing::new(&rom, platform).render(syntax));
+    Ok(())
+}
+
+fn asm(source: &str, output: Option<String>, platform: Platform) -> Result<(), String> {
+    let text =
+        std::fs::read_to_string(source).map_err(|e| format!("unable to read {}: {}", source, e))?;
+    let program = assemble(&text, platform).map_err(|e| format!("{}:{}", source, e))?;
+    let output = output.unwrap_or_else(|| {
+        std::path::Path::new(source)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+    std::fs::write(&output, &program.rom)
+        .map_err(|e| format!("unable to write {}: {}", output, e))?;
+    println!("assembled {} bytes to {}....", program.rom.len(), output);
+    Ok(())
+}
+
+// stdout belongs to the protocol, so nothing is printed
+fn dap() -> Result<(), String> {
+    let mut session = dap::Session::stdio();
+    let launch = session.wait_for_launch()?;
+    let loaded =
+        Build::from_file(&launch.program, launch.platform.unwrap_or_default()).and_then(|build| {
+            // source stays on the platform it was assembled for
+            let platform = launch
+                .platform
+                .or_else(|| {
+                    RomDatabase::bundled()
+                        .lookup(&build.rom)
+                        .filter(|_| build.source_map.is_none())
+                        .and_then(|info| info.platform)
+                })
+                .unwrap_or_default();
+            let mut cpu = Cpu::with_platform(platform);
+            cpu.load_rom(build.rom.clone()).map_err(|e| e.to_string())?;
+            Ok((build, cpu))
+        });
+    let (build, cpu) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            session.launch_failed(&e)?;
+            return Err(e);
+        }
+    };
+    session.launched(&launch.program, &build, cpu.platform())?;
+
+    let (video, audio, input) = sdl::init(Keymap::default(), Tone::default(), Palette::default())?;
+    let scheduler = scheduler::Scheduler::new(launch.ipf.unwrap_or(scheduler::DEFAULT_IPF));
+    let mut runner = Runner::new(cpu, scheduler, build.rom.clone(), video, audio, input);
+    let mut debugger = Debugger::new();
+    if let Some(source_map) = build.source_map {
+        debugger.set_source_map(source_map.clone());
+        runner.set_source_map(source_map);
+    }
+    runner.set_dap(debugger, session);
+    runner.run()
+}
+
+// accepts addresses written as 0x050 or 80
+fn parse_address(input: &str) -> Result<u16, String> {
+    let parsed = match input.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => input.parse::<u16>(),
+    };
+    parsed.map_err(|e| format!("invalid address {}: {}", input, e))
+}
+
+fn read_font<const N: usize>(path: &str) -> Result<[u8; N], String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("unable to read {}: {}", path, e))?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format!("{} is {} bytes, expected {}", path, bytes.len(), N))
 }
 
 fn main() -> Result<(), String> {
     let args = Args::parse();
 
-    if args.rom_path.is_empty() {
+    if let Some(command) = args.command {
+        return match command {
+            Command::Disasm {
+                rom,
+                platform,
+                syntax,
+            } => disasm(&rom, platform, syntax),
+            Command::Asm {
+                source,
+                output,
+                platform,
+            } => asm(&source, output, platform),
+            Command::Dap => dap(),
+        };
+    }
+
+    let rom_path = args.rom_path.clone().unwrap_or_default();
+    if rom_path.is_empty() {
         return Ok(());
     }
 
-    let mut cpu: cpu::Cpu = cpu::Cpu::new();
+    let playback = match &args.play {
+        Some(path) => Some(Movie::from_file(path)?),
+        None => None,
+    };
+    // .8o source is assembled for the platform the cpu runs as, the rom
+    // database only knows the assembled rom so it can't pick that platform
+    let requested = args
+        .platform
+        .or_else(|| playback.as_ref().map(|movie| movie.platform));
 
     println!("Loading rom.....");
-    let rom = if let Ok(bytes_read) = std::fs::read(args.rom_path.as_str()) {
-        bytes_read
-    } else {
-        panic!("unable to read the provided rom....");
+    let build = Build::from_file(&rom_path, requested.unwrap_or_default())?;
+    if build.source_map.is_some() {
+        println!("assembled {} bytes....", build.rom.len());
+    }
+    let rom = build.rom.clone();
+
+    let database = match (&args.rom_db, args.no_rom_db) {
+        (_, true) => None,
+        (Some(path), false) => Some(RomDatabase::from_file(path)?),
+        (None, false) => Some(RomDatabase::bundled()),
     };
+    let info = database.as_ref().and_then(|database| database.lookup(&rom));
+    if args.rom_info {
+        match info {
+            Some(info) => print!("{}", info),
+            None => println!(
+                "sha-1:    {}\nnot in the rom database",
+                chip8::romdb::rom_hash(&rom)
+            ),
+        }
+        return Ok(());
+    }
+    if let Some(info) = info {
+        println!("found {} in the rom database....", info.title);
+    }
 
-    cpu.load_rom(rom);
+    let platform = requested
+        .or_else(|| {
+            info.filter(|_| build.source_map.is_none())
+                .and_then(|info| info.platform)
+        })
+        .unwrap_or_default();
+    let mut cpu = Cpu::with_platform(platform);
+    // quirks recorded for the rom only apply to the platform they were recorded for
+    if args.platform.is_none() {
+        if let Some(quirks) = info.and_then(|info| info.quirks) {
+            cpu.set_quirks(quirks);
+        }
+    }
+    println!("running as {} with {:?}....", cpu.platform(), cpu.quirks());
+    cpu.set_rng_mode(args.rng);
+    cpu.set_seed(args.seed);
+    println!(
+        "random numbers from {} seeded with {}....",
+        cpu.rng_mode(),
+        cpu.seed()
+    );
+
+    if let Some(path) = &args.font {
+        cpu.load_font(read_font(path)?);
+    }
+    if let Some(path) = &args.big_font {
+        cpu.load_big_font(read_font(path)?);
+    }
+    if let Some(address) = args.font_address {
+        cpu.set_font_address(address)?;
+    }
+    if let Some(address) = args.big_font_address {
+        cpu.set_big_font_address(address)?;
+    }
+    println!(
+        "fonts are at {:#05x} and {:#05x}....",
+        cpu.font_address(),
+        cpu.big_font_address()
+    );
+
+    cpu.load_rom(rom.clone()).map_err(|e| e.to_string())?;
 
     println!("rom is loaded....");
 
     println!("Opening window....");
 
-    let sdl_context = sdl2::init()?;
-    let video_subsystem = sdl_context.video()?;
+    let keymap = match &args.keymap {
+        Some(path) => Keymap::from_file(path)?,
+        None => {
+            let mut keymap = Keymap::default();
+            if let Some(info) = info {
+                keymap.bind_hints(&info.keys);
+            }
+            keymap
+        }
+    };
+    let palette = info
+        .map(|info| Palette::from_colors(&info.colors))
+        .unwrap_or_default();
+    let tone = Tone {
+        waveform: args.waveform,
+        frequency: args.tone_hz,
+        volume: args.volume,
+    };
+    let (video, audio, input) = sdl::init(keymap, tone, palette)?;
 
-    let window = video_subsystem
-        .window("Chip-8 Rust Emulator", 768, 384)
-        .position_centered()
-        .build()
-        .map_err(|e| e.to_string())?;
+    println!("window is now opened....");
 
-    let mut canvas = window
-        .into_canvas()
-        .present_vsync()
-        .build()
-        .map_err(|e| e.to_string())?;
-    canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
-    canvas.clear();
-    canvas.present();
+    if let Some(movie) = &playback {
+        movie.apply(&mut cpu)?;
+    }
 
-    println!("window is now opened....");
+    let scheduler = match (&playback, args.clock_hz) {
+        (Some(movie), _) => scheduler::Scheduler::new(movie.ipf),
+        (None, Some(clock_hz)) => scheduler::Scheduler::from_clock_hz(clock_hz),
+        (None, None) => scheduler::Scheduler::new(
+            args.ipf
+                .or_else(|| info.and_then(|info| info.tickrate))
+                .unwrap_or(scheduler::DEFAULT_IPF),
+        ),
+    };
+    println!(
+        "running {} instructions per frame....",
+        scheduler.instructions_per_frame()
+    );
 
-    let mut event_pump = sdl_context.event_pump()?;
-
-    'running: loop {
-        canvas.set_draw_color(sdl2::pixels::Color::RGB(0, 0, 0));
-        canvas.clear();
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => break 'running,
-                _ => {}
-            }
+    let mut runner = Runner::new(cpu, scheduler, rom, video, audio, input);
+    runner.set_state_path(&rom_path);
+    if args.rewind_seconds > 0 {
+        runner.set_rewind(Rewind::from_seconds(
+            args.rewind_budget * 1024 * 1024,
+            args.rewind_seconds,
+        ));
+    }
+    if args.record.is_some() {
+        let ipf = runner.scheduler.instructions_per_frame();
+        runner.record(Movie::new(&runner.cpu, ipf)?);
+    }
+    if let Some(movie) = playback {
+        println!("playing back {} frames....", movie.frames.len());
+        runner.play(movie);
+    }
+    if args.watch {
+        println!("watching {} for changes....", rom_path);
+        let watcher = Watcher::new(&rom_path, platform, build.clone());
+        runner.set_watcher(watcher, args.watch_restore);
+    }
+    if args.debug || args.gdb.is_some() {
+        let mut debugger = Debugger::new();
+        if let Some(source_map) = &build.source_map {
+            debugger.set_source_map(source_map.clone());
         }
-        cpu.step();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
-        for i in 0..64 {
-            for j in 0..32 {
-                if cpu.vram[i][j] > 0 {
-                    canvas.fill_rect(Rect::new(i as i32 * 12, j as i32 * 12, 12, 12))?;
-                }
-            }
+        match args.gdb {
+            Some(port) => runner.set_gdb(debugger, gdb::Session::listen(port)?),
+            None => runner.set_debugger(debugger, debugger::stdin_commands()),
         }
-        canvas.present();
     }
+    if let Some(source_map) = build.source_map {
+        runner.set_source_map(source_map);
+    }
+    runner.run()?;
 
-    // loop {
-    //     cpu.step();
-    // }
+    if let (Some(path), Some(movie)) = (&args.record, runner.finish_recording()) {
+        movie.save(path)?;
+        println!("recorded {} frames to {}....", movie.frames.len(), path);
+    }
+    if let Some(Err(e)) = runner.playback() {
+        return Err(e.clone());
+    }
 
     Ok(())
 }
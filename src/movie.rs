@@ -0,0 +1,303 @@
+// input movies, the keypad state of every frame of a run so it can be
+// played back later and end in exactly the same place
+//
+// every number is little endian
+//
+//  magic       4 bytes  "C8MV"
+//  version     u16
+//  rom hash    40 bytes lowercase hex sha-1
+//  platform    u8 length, then the --platform name
+//  rng         u8 length, then the --rng name
+//  seed        u64
+//  quirks      u8, one bit per quirk in the order of the Quirks fields
+//  ipf         u32
+//  frames      u32 count, then a u16 keypad mask per frame
+//  end state   16 registers, i (u16), pc (u16), sha-1 of the display (40 bytes)
+
+use crate::bytes::Reader;
+use crate::cpu::{Cpu, HIRES_HEIGHT, HIRES_WIDTH};
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use crate::rng::RngMode;
+
+const MAGIC: &[u8; 4] = b"C8MV";
+const VERSION: u16 = 1;
+const HASH_LEN: usize = 40;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: String,
+    pub platform: Platform,
+    pub rng_mode: RngMode,
+    pub seed: u64,
+    pub quirks: Quirks,
+    pub ipf: u32,
+    // keypad mask at the start of every frame
+    pub frames: Vec<u16>,
+    pub end_state: EndState,
+}
+
+// what the machine looked like once the last frame ran
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EndState {
+    pub registers: [u8; 0x10],
+    pub i: u16,
+    pub pc: u16,
+    pub display_hash: String,
+}
+
+impl EndState {
+    pub fn of(cpu: &Cpu) -> Self {
+        let mut display = Vec::with_capacity(HIRES_WIDTH * HIRES_HEIGHT + 1);
+        display.push(cpu.hires() as u8);
+        for x in 0..HIRES_WIDTH {
+            for y in 0..HIRES_HEIGHT {
+                display.push(cpu.pixel(x, y));
+            }
+        }
+        Self {
+            registers: *cpu.registers(),
+            i: cpu.i(),
+            pc: cpu.pc(),
+            display_hash: sha1_smol::Sha1::from(display).digest().to_string(),
+        }
+    }
+}
+
+impl Movie {
+    // start recording a run of the rom loaded into the cpu
+    pub fn new(cpu: &Cpu, ipf: u32) -> Result<Self, String> {
+        let rom_hash = cpu.rom_hash().ok_or("no rom loaded to record")?;
+        Ok(Self {
+            rom_hash: rom_hash.to_string(),
+            platform: cpu.platform(),
+            rng_mode: cpu.rng_mode(),
+            seed: cpu.seed(),
+            quirks: cpu.quirks(),
+            ipf,
+            frames: Vec::new(),
+            end_state: EndState::default(),
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("unable to read {}: {}", path, e))?;
+        Self::parse(&bytes).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| format!("unable to write {}: {}", path, e))
+    }
+
+    // set the cpu up the way it was when recording started
+    // the rom has to be loaded already
+    pub fn apply(&self, cpu: &mut Cpu) -> Result<(), String> {
+        if cpu.rom_hash() != Some(self.rom_hash.as_str()) {
+            return Err(format!(
+                "the movie was recorded with the rom {}",
+                self.rom_hash
+            ));
+        }
+        if cpu.platform() != self.platform {
+            return Err(format!(
+                "the movie was recorded as {}, running as {}",
+                self.platform,
+                cpu.platform()
+            ));
+        }
+        cpu.set_quirks(self.quirks);
+        cpu.set_rng_mode(self.rng_mode);
+        cpu.set_seed(self.seed);
+        Ok(())
+    }
+
+    pub fn finish(&mut self, cpu: &Cpu) {
+        self.end_state = EndState::of(cpu);
+    }
+
+    // compare the cpu with the recorded end state once playback is over
+    pub fn check(&self, cpu: &Cpu) -> Result<(), String> {
+        let end_state = EndState::of(cpu);
+        if end_state == self.end_state {
+            return Ok(());
+        }
+        let mut differences = Vec::new();
+        if end_state.registers != self.end_state.registers {
+            differences.push(format!(
+                "registers {:02x?}, recorded {:02x?}",
+                end_state.registers, self.end_state.registers
+            ));
+        }
+        if end_state.i != self.end_state.i {
+            differences.push(format!(
+                "i {:#05x}, recorded {:#05x}",
+                end_state.i, self.end_state.i
+            ));
+        }
+        if end_state.pc != self.end_state.pc {
+            differences.push(format!(
+                "pc {:#05x}, recorded {:#05x}",
+                end_state.pc, self.end_state.pc
+            ));
+        }
+        if end_state.display_hash != self.end_state.display_hash {
+            differences.push("the display differs".to_string());
+        }
+        Err(format!("playback desynced: {}", differences.join(", ")))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(128 + self.frames.len() * 2);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(self.rom_hash.as_bytes());
+        for name in [self.platform.to_string(), self.rng_mode.to_string()] {
+            out.push(name.len() as u8);
+            out.extend_from_slice(name.as_bytes());
+        }
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        out.push(quirks_to_bits(&self.quirks));
+        out.extend_from_slice(&self.ipf.to_le_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for mask in &self.frames {
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+        out.extend_from_slice(&self.end_state.registers);
+        out.extend_from_slice(&self.end_state.i.to_le_bytes());
+        out.extend_from_slice(&self.end_state.pc.to_le_bytes());
+        let mut display_hash = [0x0; HASH_LEN];
+        let hash = self.end_state.display_hash.as_bytes();
+        display_hash[..hash.len().min(HASH_LEN)].copy_from_slice(&hash[..hash.len().min(HASH_LEN)]);
+        out.extend_from_slice(&display_hash);
+        out
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader::new(bytes, "movie");
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err("not a movie".to_string());
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(format!(
+                "movie version {} is not supported, expected {}",
+                version, VERSION
+            ));
+        }
+        let rom_hash = reader.string(HASH_LEN)?;
+        let len = reader.u8()? as usize;
+        let platform = reader.string(len)?.parse()?;
+        let len = reader.u8()? as usize;
+        let rng_mode = reader.string(len)?.parse()?;
+        let seed = reader.u64()?;
+        let quirks = quirks_from_bits(reader.u8()?);
+        let ipf = reader.u32()?;
+        let count = reader.u32()? as usize;
+        let mut frames = Vec::with_capacity(count.min(bytes.len() / 2));
+        for _ in 0..count {
+            frames.push(reader.u16()?);
+        }
+        let end_state = EndState {
+            registers: reader.array()?,
+            i: reader.u16()?,
+            pc: reader.u16()?,
+            display_hash: reader.string(HASH_LEN)?,
+        };
+        if !reader.is_empty() {
+            return Err("movie has trailing bytes".to_string());
+        }
+        Ok(Self {
+            rom_hash,
+            platform,
+            rng_mode,
+            seed,
+            quirks,
+            ipf,
+            frames,
+            end_state,
+        })
+    }
+}
+
+fn quirks_to_bits(quirks: &Quirks) -> u8 {
+    [
+        quirks.shift_uses_vy,
+        quirks.load_store_increments_i,
+        quirks.jump_uses_vx,
+        quirks.vf_reset,
+        quirks.clip_sprites,
+        quirks.display_wait,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (n, set)| bits | (*set as u8) << n)
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let bit = |n: u8| bits & (1 << n) > 0;
+    Quirks {
+        shift_uses_vy: bit(0),
+        load_store_increments_i: bit(1),
+        jump_uses_vx: bit(2),
+        vf_reset: bit(3),
+        clip_sprites: bit(4),
+        display_wait: bit(5),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn movie() -> Movie {
+        let mut cpu = Cpu::with_platform(Platform::CosmacVip);
+        cpu.set_seed(99);
+        cpu.load_rom([0x12, 0x00].to_vec()).unwrap();
+        let mut movie = Movie::new(&cpu, 15).unwrap();
+        movie.frames = vec![0x0000, 0x0010, 0x8001];
+        movie.finish(&cpu);
+        movie
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let movie = movie();
+        let bytes = movie.to_bytes();
+        assert_eq!(&bytes[0..4], b"C8MV");
+        assert_eq!(Movie::parse(&bytes), Ok(movie));
+    }
+
+    #[test]
+    fn test_quirk_bits() {
+        for quirks in [Quirks::default(), Quirks::COSMAC_VIP, Quirks::XO_CHIP] {
+            assert_eq!(quirks_from_bits(quirks_to_bits(&quirks)), quirks);
+        }
+    }
+
+    #[test]
+    fn test_bad_movies() {
+        let bytes = movie().to_bytes();
+        assert_eq!(Movie::parse(b"C8SS"), Err("not a movie".to_string()));
+        assert_eq!(
+            Movie::parse(&bytes[..bytes.len() - 1]),
+            Err("movie is truncated".to_string())
+        );
+    }
+
+    #[test]
+    fn test_apply_and_check() {
+        let movie = movie();
+        let mut cpu = Cpu::with_platform(Platform::CosmacVip);
+        cpu.load_rom([0x12, 0x00].to_vec()).unwrap();
+        movie.apply(&mut cpu).unwrap();
+        assert_eq!(cpu.seed(), 99);
+        assert_eq!(movie.check(&cpu), Ok(()));
+        cpu.set_register(3, 1);
+        assert!(movie.check(&cpu).is_err());
+
+        let mut other = Cpu::with_platform(Platform::CosmacVip);
+        other.load_rom([0x12, 0x02].to_vec()).unwrap();
+        assert!(movie.apply(&mut other).is_err());
+    }
+}
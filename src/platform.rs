@@ -0,0 +1,95 @@
+// the instruction set a rom was written for, and the quirks of the
+// interpreter it was written against
+
+use crate::quirks::Quirks;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    // the original instruction set without any interpreter quirks, how most
+    // modern chip-8 roms expect to be run
+    #[default]
+    Chip8,
+    // chip-8 on the cosmac vip, the original interpreter
+    CosmacVip,
+    // chip-48 on the hp-48 calculators
+    Chip48,
+    // super-chip 1.0, adds the 128x64 high resolution mode, 16x16 sprites
+    // and the rpl flags
+    SuperChip10,
+    // super-chip 1.1, adds the 128x64 high resolution mode, scrolling,
+    // 16x16 sprites, the large font and the rpl flags
+    SuperChip,
+    // xo-chip, super-chip plus 64 KiB of ram, two bit planes, a 16 bit
+    // index register and audio patterns
+    XoChip,
+}
+
+impl Platform {
+    pub fn supports_superchip(&self) -> bool {
+        matches!(
+            self,
+            Platform::SuperChip10 | Platform::SuperChip | Platform::XoChip
+        )
+    }
+
+    // scrolling and the large font arrived with super-chip 1.1
+    pub fn supports_superchip_1_1(&self) -> bool {
+        matches!(self, Platform::SuperChip | Platform::XoChip)
+    }
+
+    pub fn supports_xochip(&self) -> bool {
+        matches!(self, Platform::XoChip)
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::default(),
+            Platform::CosmacVip => Quirks::COSMAC_VIP,
+            Platform::Chip48 => Quirks::CHIP_48,
+            Platform::SuperChip10 => Quirks::SUPERCHIP_1_0,
+            Platform::SuperChip => Quirks::SUPERCHIP_1_1,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+
+    pub fn ram_size(&self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+}
+
+impl FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Platform::Chip8),
+            "vip" | "cosmac-vip" => Ok(Platform::CosmacVip),
+            "chip48" | "chip-48" => Ok(Platform::Chip48),
+            "schip1.0" | "superchip1.0" => Ok(Platform::SuperChip10),
+            "schip" | "superchip" | "schip1.1" | "superchip1.1" => Ok(Platform::SuperChip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            _ => Err(format!(
+                "unknown platform {}, expected chip8, vip, chip48, schip1.0, schip1.1 or xochip",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Platform::Chip8 => write!(f, "chip8"),
+            Platform::CosmacVip => write!(f, "vip"),
+            Platform::Chip48 => write!(f, "chip48"),
+            Platform::SuperChip10 => write!(f, "schip1.0"),
+            Platform::SuperChip => write!(f, "schip1.1"),
+            Platform::XoChip => write!(f, "xochip"),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// behaviours that differ between chip-8 interpreters
+// the defaults are the common modern interpretation, every flag off
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    // 8xy6 / 8xye shift vy into vx instead of shifting vx in place
+    pub shift_uses_vy: bool,
+    // fx55 / fx65 leave i pointing past the last register stored or loaded
+    pub load_store_increments_i: bool,
+    // bnnn jumps to nnn + vx, x being the top nibble of nnn, instead of nnn + v0
+    pub jump_uses_vx: bool,
+    // 8xy1 / 8xy2 / 8xy3 set vf to 0
+    pub vf_reset: bool,
+    // sprites are cut off at the edge of the display instead of wrapping around
+    pub clip_sprites: bool,
+    // dxyn waits for the start of the next frame before drawing
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        vf_reset: true,
+        clip_sprites: true,
+        display_wait: true,
+    };
+
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        jump_uses_vx: true,
+        vf_reset: false,
+        clip_sprites: true,
+        display_wait: false,
+    };
+
+    pub const SUPERCHIP_1_0: Quirks = Quirks::CHIP_48;
+
+    pub const SUPERCHIP_1_1: Quirks = Quirks::CHIP_48;
+
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        jump_uses_vx: false,
+        vf_reset: false,
+        clip_sprites: false,
+        display_wait: false,
+    };
+}
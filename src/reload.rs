@@ -0,0 +1,169 @@
+// rebuilding the rom when its file changes on disk
+//
+// the file's modification time is polled, a change reads the file again and
+// .8o source is assembled again, a build that fails leaves the old one running
+
+use crate::asm::{self, SourceMap};
+use crate::platform::Platform;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Build {
+    pub rom: Vec<u8>,
+    // only for programs assembled from source
+    pub source_map: Option<SourceMap>,
+    pub labels: BTreeMap<String, u16>,
+}
+
+impl Build {
+    // files ending in .8o are octo source, assembled for the platform
+    pub fn from_file(path: &str, platform: Platform) -> Result<Self, String> {
+        if path.ends_with(".8o") {
+            let source = std::fs::read_to_string(path)
+                .map_err(|e| format!("unable to read {}: {}", path, e))?;
+            let program =
+                asm::assemble(&source, platform).map_err(|e| format!("{}:{}", path, e))?;
+            return Ok(Self {
+                rom: program.rom,
+                source_map: Some(program.source_map),
+                labels: program.labels,
+            });
+        }
+        let rom = std::fs::read(path).map_err(|e| format!("unable to read {}: {}", path, e))?;
+        Ok(Self {
+            rom,
+            source_map: None,
+            labels: BTreeMap::new(),
+        })
+    }
+
+    // a save state of one build suits the other when the rom kept its length
+    // and every label kept its address
+    pub fn same_layout(&self, other: &Build) -> bool {
+        self.rom.len() == other.rom.len() && self.labels == other.labels
+    }
+}
+
+pub struct Reload {
+    pub build: Build,
+    pub same_layout: bool,
+}
+
+pub struct Watcher {
+    path: String,
+    platform: Platform,
+    interval: Duration,
+    last_poll: Option<Instant>,
+    modified: Option<SystemTime>,
+    // labels and length of the running build, for the layout check
+    build: Build,
+}
+
+impl Watcher {
+    // the build is what the file holds now
+    pub fn new(path: &str, platform: Platform, build: Build) -> Self {
+        Self {
+            path: path.to_string(),
+            platform,
+            interval: POLL_INTERVAL,
+            last_poll: None,
+            modified: modified(path),
+            build,
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    // the new build once the file changed, the file is looked at no more
+    // often than the interval
+    pub fn poll(&mut self, now: Instant) -> Option<Result<Reload, String>> {
+        if self
+            .last_poll
+            .is_some_and(|last_poll| now.duration_since(last_poll) < self.interval)
+        {
+            return None;
+        }
+        self.last_poll = Some(now);
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(Build::from_file(&self.path, self.platform).map(|build| {
+            let same_layout = build.same_layout(&self.build);
+            self.build = build.clone();
+            Reload { build, same_layout }
+        }))
+    }
+}
+
+// None while the file is missing, editors often replace it in two steps
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &std::path::Path, contents: &str, secs: u64) {
+        std::fs::write(path, contents).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_watcher() {
+        let dir = std::env::temp_dir().join(format!("chip8-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rom.8o");
+        touch(&path, ": main v0 := 1 jump main", 1);
+        let path = path.to_str().unwrap();
+
+        let build = Build::from_file(path, Platform::Chip8).unwrap();
+        assert_eq!(build.rom, [0x60, 0x01, 0x12, 0x00]);
+        assert_eq!(build.labels.get("main"), Some(&0x200));
+        let mut watcher = Watcher::new(path, Platform::Chip8, build);
+        let start = Instant::now();
+        assert!(watcher.poll(start).is_none());
+
+        // same layout, the label stays put
+        touch(dir.join("rom.8o").as_path(), ": main v0 := 2 jump main", 2);
+        assert!(watcher.poll(start).is_none());
+        let reload = watcher.poll(start + POLL_INTERVAL).unwrap().unwrap();
+        assert_eq!(reload.build.rom, [0x60, 0x02, 0x12, 0x00]);
+        assert!(reload.same_layout);
+        assert!(watcher.poll(start + POLL_INTERVAL * 2).is_none());
+
+        // a label moved
+        watcher.set_interval(Duration::ZERO);
+        touch(
+            dir.join("rom.8o").as_path(),
+            "v1 := 0 : main v0 := 2 jump main",
+            3,
+        );
+        let reload = watcher.poll(start).unwrap().unwrap();
+        assert!(!reload.same_layout);
+
+        // a broken build is reported and the next good one compared to the last good one
+        touch(dir.join("rom.8o").as_path(), "v1 := nowhere", 4);
+        assert!(watcher.poll(start).unwrap().is_err());
+        touch(
+            dir.join("rom.8o").as_path(),
+            "v1 := 1 : main v0 := 3 jump main",
+            5,
+        );
+        assert!(watcher.poll(start).unwrap().unwrap().same_layout);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,188 @@
+// rewind buffer, a ring of save states taken every frame
+//
+// only the newest state is kept whole, every older one is stored as the
+// xor of it and the state after it, run length encoded, most of a frame to
+// frame delta is zeros so each one takes a few hundred bytes
+// the oldest deltas are dropped to stay inside the memory budget and frame limit
+
+use crate::scheduler::FRAME_RATE;
+use std::collections::VecDeque;
+
+pub const DEFAULT_SECONDS: u32 = 10;
+pub const DEFAULT_BUDGET: usize = 16 * 1024 * 1024;
+
+pub struct Rewind {
+    latest: Option<Vec<u8>>,
+    // newest at the back, each one turns the state after it into itself
+    deltas: VecDeque<Vec<u8>>,
+    // bytes held by the deltas and the latest state
+    used: usize,
+    budget: usize,
+    max_frames: usize,
+}
+
+impl Rewind {
+    pub fn new(budget: usize, max_frames: usize) -> Self {
+        Self {
+            latest: None,
+            deltas: VecDeque::new(),
+            used: 0,
+            budget,
+            max_frames,
+        }
+    }
+
+    pub fn from_seconds(budget: usize, seconds: u32) -> Self {
+        Self::new(budget, (seconds * FRAME_RATE) as usize)
+    }
+
+    // number of states that can be popped
+    pub fn len(&self) -> usize {
+        self.deltas.len() + self.latest.is_some() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.latest.is_none()
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.used
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.used = 0;
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        match self.latest.take() {
+            // states only differ in length when the platform changed
+            Some(latest) if latest.len() == state.len() => {
+                let delta = encode(&xor(&latest, &state));
+                self.used += delta.len() + state.len() - latest.len();
+                self.deltas.push_back(delta);
+            }
+            _ => {
+                self.clear();
+                self.used = state.len();
+            }
+        }
+        self.latest = Some(state);
+        while self.len() > self.max_frames.max(1) || self.used > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    // take the newest state, the one before it becomes the newest
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let latest = self.latest.take()?;
+        self.used -= latest.len();
+        if let Some(delta) = self.deltas.pop_back() {
+            self.used -= delta.len();
+            let previous = xor(&latest, &decode(&delta));
+            self.used += previous.len();
+            self.latest = Some(previous);
+        }
+        Some(latest)
+    }
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(a, b)| a ^ b).collect()
+}
+
+// pairs of run length (1 - 255) and byte
+fn encode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut iter = bytes.iter().peekable();
+    while let Some(&byte) = iter.next() {
+        let mut run = 1u8;
+        while run < u8::MAX && iter.peek() == Some(&&byte) {
+            iter.next();
+            run += 1;
+        }
+        out.push(run);
+        out.push(byte);
+    }
+    out
+}
+
+fn decode(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for pair in bytes.chunks_exact(2) {
+        out.extend(std::iter::repeat_n(pair[1], pair[0] as usize));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(n: u8) -> Vec<u8> {
+        let mut state = vec![0x0; 1000];
+        state[10] = n;
+        state[500] = n.wrapping_mul(3);
+        state
+    }
+
+    #[test]
+    fn test_rle_round_trip() {
+        let bytes = [0, 0, 0, 1, 2, 2, 0, 0];
+        assert_eq!(encode(&bytes), vec![3, 0, 1, 1, 2, 2, 2, 0]);
+        assert_eq!(decode(&encode(&bytes)), bytes);
+        let long = vec![7; 600];
+        assert_eq!(encode(&long).len(), 6);
+        assert_eq!(decode(&encode(&long)), long);
+    }
+
+    #[test]
+    fn test_pop_in_reverse_order() {
+        let mut rewind = Rewind::new(DEFAULT_BUDGET, 100);
+        for n in 0..5 {
+            rewind.push(state(n));
+        }
+        assert_eq!(rewind.len(), 5);
+        for n in (0..5).rev() {
+            assert_eq!(rewind.pop(), Some(state(n)));
+        }
+        assert_eq!(rewind.pop(), None);
+        assert_eq!(rewind.memory_used(), 0);
+    }
+
+    #[test]
+    fn test_frame_limit() {
+        let mut rewind = Rewind::new(DEFAULT_BUDGET, 3);
+        for n in 0..10 {
+            rewind.push(state(n));
+        }
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.pop(), Some(state(9)));
+        assert_eq!(rewind.pop(), Some(state(8)));
+        assert_eq!(rewind.pop(), Some(state(7)));
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut rewind = Rewind::new(1100, 100);
+        for n in 0..50 {
+            rewind.push(state(n));
+        }
+        assert!(rewind.memory_used() <= 1100);
+        assert!(rewind.len() > 1);
+        assert_eq!(rewind.pop(), Some(state(49)));
+    }
+
+    #[test]
+    fn test_deltas_are_small() {
+        let mut rewind = Rewind::new(DEFAULT_BUDGET, 100);
+        rewind.push(state(1));
+        rewind.push(state(2));
+        assert!(rewind.memory_used() < 1000 + 20);
+    }
+}
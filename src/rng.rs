@@ -0,0 +1,143 @@
+// random numbers for cxkk
+// tinyrand keeps the generator state private, this keeps it in the open so
+// save states can capture it, the numbers drawn are the ones StdRand gives
+
+use std::fmt;
+use std::str::FromStr;
+use tinyrand::{Rand, Seeded, StdRand};
+
+// wyrand moves its state on by this much every draw
+const INCREMENT: u64 = 0xa076_1d64_78bd_642f;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RngMode {
+    // wyrand from tinyrand
+    #[default]
+    Wyrand,
+    // the routine of the cosmac vip interpreter, a 16 bit counter (r9 on the
+    // vip) is bumped and the byte at 0x100 + its low byte is added to its
+    // high byte, which is the random number
+    // the vip read that page from its own interpreter code, here it holds
+    // whatever the emulator put below 0x200, so the numbers only match the
+    // vip if the rom leaves the same bytes there
+    CosmacVip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rng {
+    mode: RngMode,
+    // the wyrand state, or the vip counter in the low 16 bits
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self::with_mode(seed, RngMode::Wyrand)
+    }
+
+    pub fn with_mode(seed: u64, mode: RngMode) -> Self {
+        let state = match mode {
+            RngMode::Wyrand => seed,
+            RngMode::CosmacVip => seed & 0xffff,
+        };
+        Self { mode, state }
+    }
+
+    pub fn mode(&self) -> RngMode {
+        self.mode
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn set_state(&mut self, state: u64) {
+        self.state = state;
+    }
+
+    pub fn next_u16(&mut self) -> u16 {
+        let value = StdRand::seed(self.state).next_u16();
+        self.state = self.state.wrapping_add(INCREMENT);
+        value
+    }
+
+    // the byte cxkk masks, ram is only read in vip mode
+    pub fn next_u8(&mut self, ram: &[u8]) -> u8 {
+        match self.mode {
+            RngMode::Wyrand => self.next_u16() as u8,
+            RngMode::CosmacVip => {
+                let counter = (self.state as u16).wrapping_add(1);
+                let table = ram[0x100 + (counter & 0xff) as usize];
+                let value = ((counter >> 8) as u8).wrapping_add(table);
+                self.state = (value as u64) << 8 | (counter & 0xff) as u64;
+                value
+            }
+        }
+    }
+}
+
+impl FromStr for RngMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "wyrand" => Ok(RngMode::Wyrand),
+            "vip" | "cosmac-vip" => Ok(RngMode::CosmacVip),
+            _ => Err(format!("unknown rng {}, expected wyrand or vip", s)),
+        }
+    }
+}
+
+impl fmt::Display for RngMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RngMode::Wyrand => write!(f, "wyrand"),
+            RngMode::CosmacVip => write!(f, "vip"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_numbers_as_std_rand() {
+        let mut rng = Rng::default();
+        let mut std_rand = StdRand::default();
+        for _ in 0..10 {
+            assert_eq!(rng.next_u16(), std_rand.next_u16());
+        }
+    }
+
+    #[test]
+    fn test_restore_state() {
+        let mut rng = Rng::new(42);
+        rng.next_u16();
+        let state = rng.state();
+        let expected = [rng.next_u16(), rng.next_u16()];
+        rng.set_state(state);
+        assert_eq!([rng.next_u16(), rng.next_u16()], expected);
+    }
+
+    #[test]
+    fn test_seeds_differ() {
+        let ram = [0x0; 0x200];
+        let mut a = Rng::new(1);
+        let mut b = Rng::new(2);
+        let a: Vec<u8> = (0..8).map(|_| a.next_u8(&ram)).collect();
+        let b: Vec<u8> = (0..8).map(|_| b.next_u8(&ram)).collect();
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_cosmac_vip() {
+        let mut ram = [0x0; 0x200];
+        ram[0x101] = 0x10;
+        ram[0x102] = 0x25;
+        let mut rng = Rng::with_mode(0x0000, RngMode::CosmacVip);
+        assert_eq!(rng.next_u8(&ram), 0x10);
+        assert_eq!(rng.next_u8(&ram), 0x35);
+        assert_eq!(rng.state(), 0x3502);
+    }
+}
@@ -0,0 +1,337 @@
+// rom database, settings for known roms looked up by the sha-1 of the rom
+//
+// reads the programs.json file of the community chip-8-database
+// (https://github.com/chip-8/chip-8-database), a list of programs each with
+// a map of rom hashes to the platforms, quirks, tickrate, colours and keys
+// the rom wants
+// the bundled copy only knows a few public domain roms, drop the community
+// programs.json into data/ or point --rom-db at it
+
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const BUNDLED: &str = include_str!("../data/programs.json");
+
+// lowercase hex sha-1 of the rom, the key used by the database
+pub fn rom_hash(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+#[derive(Deserialize)]
+struct ProgramEntry {
+    title: String,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    file: Option<String>,
+    #[serde(default)]
+    platforms: Vec<String>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirksEntry>,
+    tickrate: Option<u32>,
+    colors: Option<ColorsEntry>,
+    #[serde(default)]
+    keys: BTreeMap<String, u8>,
+}
+
+// every flag is true when the platform behaves the quirky way
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirksEntry {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    vblank: Option<bool>,
+    logic: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ColorsEntry {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+// everything the database knows about one rom
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub hash: String,
+    pub file: Option<String>,
+    // the first platform listed that the emulator supports
+    pub platform: Option<Platform>,
+    // the platform presets with any overrides the rom asks for
+    pub quirks: Option<Quirks>,
+    // instructions per frame
+    pub tickrate: Option<u32>,
+    // pixel colours, indexed by the plane bits of a pixel
+    pub colors: Vec<(u8, u8, u8)>,
+    // what the rom uses its keypad keys for, "up" = 5
+    pub keys: BTreeMap<String, u8>,
+}
+
+pub struct RomDatabase {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDatabase {
+    pub fn bundled() -> Self {
+        Self::parse(BUNDLED).expect("the bundled rom database is valid")
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|e| format!("unable to read {}: {}", path, e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let programs: Vec<ProgramEntry> =
+            serde_json::from_str(json).map_err(|e| format!("invalid rom database: {}", e))?;
+        let mut roms = HashMap::new();
+        for program in programs {
+            for (hash, rom) in program.roms {
+                let hash = hash.to_lowercase();
+                let info = RomInfo::new(&program.title, &hash, rom)?;
+                roms.insert(hash, info);
+            }
+        }
+        Ok(Self { roms })
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.lookup_hash(&rom_hash(rom))
+    }
+
+    pub fn lookup_hash(&self, hash: &str) -> Option<&RomInfo> {
+        self.roms.get(&hash.to_lowercase())
+    }
+}
+
+impl RomInfo {
+    fn new(title: &str, hash: &str, rom: RomEntry) -> Result<Self, String> {
+        let supported = rom
+            .platforms
+            .iter()
+            .find_map(|id| platform_from_id(id).map(|platform| (id, platform)));
+        let quirks = supported.map(|(id, platform)| {
+            let mut quirks = platform.quirks();
+            if let Some(overrides) = rom.quirky_platforms.get(id) {
+                overrides.apply(&mut quirks);
+            }
+            quirks
+        });
+        let colors = match rom.colors {
+            Some(colors) => colors
+                .pixels
+                .iter()
+                .map(|color| parse_color(color))
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        Ok(Self {
+            title: title.to_string(),
+            hash: hash.to_string(),
+            file: rom.file,
+            platform: supported.map(|(_, platform)| platform),
+            quirks,
+            tickrate: rom.tickrate,
+            colors,
+            keys: rom.keys,
+        })
+    }
+}
+
+impl QuirksEntry {
+    fn apply(&self, quirks: &mut Quirks) {
+        if let Some(shift) = self.shift {
+            quirks.shift_uses_vy = !shift;
+        }
+        // incrementing by x instead of x + 1 is not emulated, the closest
+        // behaviour is leaving i alone
+        if let Some(true) = self.memory_increment_by_x {
+            quirks.load_store_increments_i = false;
+        }
+        if let Some(leave) = self.memory_leave_i_unchanged {
+            quirks.load_store_increments_i = !leave;
+        }
+        if let Some(wrap) = self.wrap {
+            quirks.clip_sprites = !wrap;
+        }
+        if let Some(jump) = self.jump {
+            quirks.jump_uses_vx = jump;
+        }
+        if let Some(vblank) = self.vblank {
+            quirks.display_wait = vblank;
+        }
+        if let Some(logic) = self.logic {
+            quirks.vf_reset = logic;
+        }
+    }
+}
+
+// platform ids used by the database
+fn platform_from_id(id: &str) -> Option<Platform> {
+    match id {
+        "originalChip8" | "hybridVIP" => Some(Platform::CosmacVip),
+        "modernChip8" => Some(Platform::Chip8),
+        "chip48" => Some(Platform::Chip48),
+        "superchip1" => Some(Platform::SuperChip10),
+        "superchip" => Some(Platform::SuperChip),
+        "xochip" => Some(Platform::XoChip),
+        _ => None,
+    }
+}
+
+// #rrggbb
+fn parse_color(color: &str) -> Result<(u8, u8, u8), String> {
+    let invalid = || format!("invalid colour {}, expected #rrggbb", color);
+    let hex = color.strip_prefix('#').ok_or_else(invalid)?;
+    if hex.len() != 6 {
+        return Err(invalid());
+    }
+    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).map_err(|_| invalid());
+    Ok((channel(0)?, channel(2)?, channel(4)?))
+}
+
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "title:    {}", self.title)?;
+        writeln!(f, "sha-1:    {}", self.hash)?;
+        if let Some(file) = &self.file {
+            writeln!(f, "file:     {}", file)?;
+        }
+        match self.platform {
+            Some(platform) => writeln!(f, "platform: {}", platform)?,
+            None => writeln!(f, "platform: none of the listed platforms are supported")?,
+        }
+        if let Some(quirks) = &self.quirks {
+            writeln!(f, "quirks:   {:?}", quirks)?;
+        }
+        if let Some(tickrate) = self.tickrate {
+            writeln!(f, "tickrate: {} instructions per frame", tickrate)?;
+        }
+        if !self.colors.is_empty() {
+            let colors: Vec<String> = self
+                .colors
+                .iter()
+                .map(|(r, g, b)| format!("#{:02x}{:02x}{:02x}", r, g, b))
+                .collect();
+            writeln!(f, "colours:  {}", colors.join(" "))?;
+        }
+        if !self.keys.is_empty() {
+            let keys: Vec<String> = self
+                .keys
+                .iter()
+                .map(|(name, key)| format!("{}={:x}", name, key))
+                .collect();
+            writeln!(f, "keys:     {}", keys.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATABASE: &str = r##"[
+        {
+            "title": "Test Rom",
+            "description": "ignored",
+            "roms": {
+                "A9993E364706816ABA3E25717850C26C9CD0D89D": {
+                    "file": "test.ch8",
+                    "platforms": ["megachip8", "superchip", "xochip"],
+                    "quirkyPlatforms": {
+                        "superchip": { "shift": false, "vblank": true }
+                    },
+                    "tickrate": 30,
+                    "colors": { "pixels": ["#000000", "#ff8000"] },
+                    "keys": { "up": 5, "down": 8 }
+                }
+            }
+        }
+    ]"##;
+
+    #[test]
+    fn test_rom_hash() {
+        assert_eq!(rom_hash(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn test_lookup() {
+        let database = RomDatabase::parse(DATABASE).unwrap();
+        assert_eq!(database.len(), 1);
+        let info = database.lookup(b"abc").unwrap();
+        assert_eq!(info.title, "Test Rom");
+        assert_eq!(info.file.as_deref(), Some("test.ch8"));
+        assert_eq!(info.platform, Some(Platform::SuperChip));
+        let quirks = info.quirks.unwrap();
+        assert!(quirks.shift_uses_vy);
+        assert!(quirks.display_wait);
+        assert!(quirks.jump_uses_vx);
+        assert_eq!(info.tickrate, Some(30));
+        assert_eq!(info.colors, vec![(0, 0, 0), (0xff, 0x80, 0x00)]);
+        assert_eq!(info.keys.get("up"), Some(&5));
+        assert!(database.lookup(b"abd").is_none());
+    }
+
+    #[test]
+    fn test_unsupported_platform() {
+        let database = RomDatabase::parse(
+            r#"[{ "title": "t", "roms": { "00": { "platforms": ["megachip8"] } } }]"#,
+        )
+        .unwrap();
+        let info = database.lookup_hash("00").unwrap();
+        assert_eq!(info.platform, None);
+        assert_eq!(info.quirks, None);
+    }
+
+    #[test]
+    fn test_invalid_database() {
+        assert!(RomDatabase::parse("{}").is_err());
+        assert!(RomDatabase::parse(
+            r#"[{ "title": "t", "roms": { "00": { "colors": { "pixels": ["red"] } } } }]"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_bundled() {
+        #[rustfmt::skip]
+        let ibm_logo: [u8; 132] = [
+            0x00, 0xe0, 0xa2, 0x2a, 0x60, 0x0c, 0x61, 0x08, 0xd0, 0x1f, 0x70, 0x09,
+            0xa2, 0x39, 0xd0, 0x1f, 0xa2, 0x48, 0x70, 0x08, 0xd0, 0x1f, 0x70, 0x04,
+            0xa2, 0x57, 0xd0, 0x1f, 0x70, 0x08, 0xa2, 0x66, 0xd0, 0x1f, 0x70, 0x08,
+            0xa2, 0x75, 0xd0, 0x1f, 0x12, 0x28, 0xff, 0x00, 0xff, 0x00, 0x3c, 0x00,
+            0x3c, 0x00, 0x3c, 0x00, 0x3c, 0x00, 0xff, 0x00, 0xff, 0xff, 0x00, 0xff,
+            0x00, 0x38, 0x00, 0x3f, 0x00, 0x3f, 0x00, 0x38, 0x00, 0xff, 0x00, 0xff,
+            0x80, 0x00, 0xe0, 0x00, 0xe0, 0x00, 0x80, 0x00, 0x80, 0x00, 0xe0, 0x00,
+            0xe0, 0x00, 0x80, 0xf8, 0x00, 0xfc, 0x00, 0x3e, 0x00, 0x3f, 0x00, 0x3b,
+            0x00, 0x39, 0x00, 0xf8, 0x00, 0xf8, 0x03, 0x00, 0x07, 0x00, 0x0f, 0x00,
+            0xbf, 0x00, 0xfb, 0x00, 0xf3, 0x00, 0xe3, 0x00, 0x43, 0xe0, 0x00, 0xe0,
+            0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0xe0, 0x00, 0xe0,
+        ];
+        let database = RomDatabase::bundled();
+        let info = database.lookup(&ibm_logo).unwrap();
+        assert_eq!(info.title, "IBM Logo");
+        assert_eq!(info.platform, Some(Platform::CosmacVip));
+    }
+}
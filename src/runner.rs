@@ -0,0 +1,1035 @@
+// the run loop, generic over the frontend backends
+
+use crate::asm::SourceMap;
+use crate::dap;
+use crate::debugger::{self, Debugger};
+use crate::frontend::{AudioSink, InputEvent, InputSource, VideoSink};
+use crate::gdb;
+use crate::movie::Movie;
+use crate::reload::Watcher;
+use crate::rewind::Rewind;
+use crate::scheduler::Scheduler;
+use crate::{Cpu, CpuError, StepOutcome};
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+use std::time::Instant;
+
+pub struct Runner<V: VideoSink, A: AudioSink, I: InputSource> {
+    pub cpu: Cpu,
+    pub scheduler: Scheduler,
+    pub video: V,
+    pub audio: A,
+    pub input: I,
+    // reloaded into the cpu on reset
+    rom: Vec<u8>,
+    muted: bool,
+    // set once the cpu hits an error, the last frame stays on screen
+    // until the rom is reset or the frontend quits
+    halted: Option<CpuError>,
+    quit: bool,
+    // save state slot n lives in <state_path>.state<n>
+    state_path: Option<String>,
+    // a state is pushed every frame, popped every frame while rewinding
+    rewind: Option<Rewind>,
+    rewinding: bool,
+    movie: Option<Movie>,
+    // playing the movie back instead of recording it
+    playing: bool,
+    frame: usize,
+    // the end state check once playback is over
+    playback: Option<Result<(), String>>,
+    // while the debugger is paused frames only present, the cpu and
+    // timers stand still
+    debugger: Option<Debugger>,
+    debug_commands: Option<Receiver<String>>,
+    // drives the debugger instead of typed commands, stops are reported to it
+    gdb: Option<gdb::Session>,
+    // the same for an editor speaking the debug adapter protocol, which owns stdout
+    dap: Option<dap::Session>,
+    // for programs assembled from source, errors name the line
+    source_map: Option<SourceMap>,
+    // rebuilds the rom when its file changes, optionally going back to the
+    // last save state made or loaded
+    watcher: Option<Watcher>,
+    restore_on_reload: bool,
+    last_state: Option<Vec<u8>>,
+}
+
+impl<V: VideoSink, A: AudioSink, I: InputSource> Runner<V, A, I> {
+    // the rom is expected to be loaded into the cpu already
+    pub fn new(cpu: Cpu, scheduler: Scheduler, rom: Vec<u8>, video: V, audio: A, input: I) -> Self {
+        Self {
+            cpu,
+            scheduler,
+            video,
+            audio,
+            input,
+            rom,
+            muted: false,
+            halted: None,
+            quit: false,
+            state_path: None,
+            rewind: None,
+            rewinding: false,
+            movie: None,
+            playing: false,
+            frame: 0,
+            playback: None,
+            debugger: None,
+            debug_commands: None,
+            gdb: None,
+            dap: None,
+            source_map: None,
+            watcher: None,
+            restore_on_reload: false,
+            last_state: None,
+        }
+    }
+
+    // commands are read from the receiver whenever input is polled
+    pub fn set_debugger(&mut self, debugger: Debugger, commands: Receiver<String>) {
+        self.debugger = Some(debugger);
+        self.debug_commands = Some(commands);
+        if self
+            .debugger
+            .as_ref()
+            .is_some_and(|debugger| debugger.paused())
+        {
+            self.debugger_stopped();
+        }
+    }
+
+    // the debugger is driven by gdb, which finds it paused
+    pub fn set_gdb(&mut self, debugger: Debugger, session: gdb::Session) {
+        self.debugger = Some(debugger);
+        self.gdb = Some(session);
+    }
+
+    // the session has answered the launch request already
+    pub fn set_dap(&mut self, debugger: Debugger, session: dap::Session) {
+        self.debugger = Some(debugger);
+        self.dap = Some(session);
+    }
+
+    pub fn set_source_map(&mut self, source_map: SourceMap) {
+        self.source_map = Some(source_map);
+    }
+
+    // with restore the last save state goes back onto builds of the same layout
+    pub fn set_watcher(&mut self, watcher: Watcher, restore: bool) {
+        self.watcher = Some(watcher);
+        self.restore_on_reload = restore;
+    }
+
+    pub fn debugger(&self) -> Option<&Debugger> {
+        self.debugger.as_ref()
+    }
+
+    fn debugger_stopped(&mut self) {
+        if let Some(session) = &mut self.gdb {
+            let signal = match self.halted {
+                Some(_) => gdb::SIGILL,
+                None => gdb::SIGTRAP,
+            };
+            if let Err(e) = session.stopped(signal) {
+                eprintln!("{}", e);
+            }
+            return;
+        }
+        if let Some(session) = &mut self.dap {
+            if let Err(e) = session.stopped(self.halted.as_ref()) {
+                eprintln!("{}", e);
+            }
+            return;
+        }
+        if let Some(debugger) = &self.debugger {
+            print!("{}{}", debugger.describe(&self.cpu), debugger::PROMPT);
+        }
+        let _ = std::io::stdout().flush();
+    }
+
+    // a dap client owns stdout, so status messages go to its console instead
+    fn status(&mut self, message: &str) {
+        match &mut self.dap {
+            Some(session) => {
+                if let Err(e) = session.output(&format!("{}\n", message)) {
+                    eprintln!("{}", e);
+                }
+            }
+            None => println!("{}", message),
+        }
+    }
+
+    fn debug_commands(&mut self) {
+        if let (Some(session), Some(debugger)) = (&mut self.gdb, &mut self.debugger) {
+            match session.poll(debugger, &mut self.cpu) {
+                Ok(None) => {}
+                Ok(Some(gdb::Event::Quit)) => self.quit = true,
+                Ok(Some(gdb::Event::Detach)) => {
+                    println!("gdb detached....");
+                    self.gdb = None;
+                    self.debugger = None;
+                }
+                Err(e) => {
+                    eprintln!("{}, detaching....", e);
+                    self.gdb = None;
+                    self.debugger = None;
+                }
+            }
+            // a halted cpu runs nothing, so a step or continue stops right away
+            if self.halted.is_some() {
+                self.debugger_stopped();
+            }
+            return;
+        }
+        if let (Some(session), Some(debugger)) = (&mut self.dap, &mut self.debugger) {
+            match session.poll(debugger, &mut self.cpu) {
+                Ok(false) => {}
+                Ok(true) => self.quit = true,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    self.quit = true;
+                }
+            }
+            if self.halted.is_some() {
+                self.debugger_stopped();
+            }
+            return;
+        }
+        let (Some(debugger), Some(commands)) = (&mut self.debugger, &self.debug_commands) else {
+            return;
+        };
+        while let Ok(line) = commands.try_recv() {
+            print!("{}", debugger.command(&line, &mut self.cpu));
+            if debugger.quit_requested() {
+                self.quit = true;
+                return;
+            }
+            if debugger.paused() {
+                print!("{}", debugger::PROMPT);
+            }
+            let _ = std::io::stdout().flush();
+        }
+    }
+
+    // record the keypad from the next frame on
+    pub fn record(&mut self, movie: Movie) {
+        self.movie = Some(movie);
+        self.playing = false;
+        self.frame = 0;
+    }
+
+    // play the keypad of a movie back, live keypad input is ignored and the
+    // runner quits after the last frame
+    pub fn play(&mut self, movie: Movie) {
+        self.movie = Some(movie);
+        self.playing = true;
+        self.frame = 0;
+        self.playback = None;
+    }
+
+    // a recording with its end state filled in
+    pub fn finish_recording(&mut self) -> Option<Movie> {
+        if self.playing {
+            return None;
+        }
+        let mut movie = self.movie.take()?;
+        movie.finish(&self.cpu);
+        Some(movie)
+    }
+
+    // how playback ended, None while it is still going
+    pub fn playback(&self) -> Option<&Result<(), String>> {
+        self.playback.as_ref()
+    }
+
+    pub fn set_rewind(&mut self, rewind: Rewind) {
+        self.rewind = Some(rewind);
+    }
+
+    pub fn rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    // where save state slots are written, usually the rom path
+    pub fn set_state_path(&mut self, path: &str) {
+        self.state_path = Some(path.to_string());
+    }
+
+    fn slot_path(&self, slot: u8) -> Result<String, String> {
+        match &self.state_path {
+            Some(path) => Ok(format!("{}.state{}", path, slot)),
+            None => Err("no save state path set".to_string()),
+        }
+    }
+
+    pub fn save_state(&mut self, slot: u8) -> Result<(), String> {
+        let path = self.slot_path(slot)?;
+        let state = self.cpu.save_state();
+        std::fs::write(&path, &state).map_err(|e| format!("unable to write {}: {}", path, e))?;
+        self.last_state = Some(state);
+        Ok(())
+    }
+
+    pub fn load_state(&mut self, slot: u8) -> Result<(), String> {
+        let path = self.slot_path(slot)?;
+        let state = std::fs::read(&path).map_err(|e| format!("unable to read {}: {}", path, e))?;
+        self.cpu
+            .load_state(&state)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        self.last_state = Some(state);
+        self.halted = None;
+        self.video.show_error(None)
+    }
+
+    // a rom whose file changed is loaded into a reset cpu
+    fn reload(&mut self) -> Result<(), String> {
+        let Some(watcher) = &mut self.watcher else {
+            return Ok(());
+        };
+        let reload = match watcher.poll(Instant::now()) {
+            None => return Ok(()),
+            Some(Err(e)) => {
+                eprintln!("unable to reload the rom: {}", e);
+                return Ok(());
+            }
+            Some(Ok(reload)) => reload,
+        };
+        let path = watcher.path().to_string();
+        if self.movie.is_some() {
+            eprintln!(
+                "{} changed, not reloading while a movie is recording or playing....",
+                path
+            );
+            return Ok(());
+        }
+
+        let rom = reload.build.rom;
+        self.cpu.reset();
+        self.cpu.load_rom(rom.clone()).map_err(|e| e.to_string())?;
+        self.status(&format!("reloaded {}....", path));
+        if let (true, Some(state)) = (self.restore_on_reload, self.last_state.clone()) {
+            if !reload.same_layout {
+                self.status("the layout changed, not restoring the last save state....");
+            } else if let Err(e) = self.cpu.load_state_over_rom(&state, &rom) {
+                eprintln!("unable to restore the last save state: {}", e);
+                self.cpu.reset();
+                self.cpu.load_rom(rom.clone()).map_err(|e| e.to_string())?;
+            } else {
+                self.status("restored the last save state....");
+            }
+        }
+        self.rom = rom;
+        if let Some(source_map) = reload.build.source_map {
+            if let Some(debugger) = &mut self.debugger {
+                debugger.set_source_map(source_map.clone());
+            }
+            self.source_map = Some(source_map);
+        }
+        self.halted = None;
+        if let Some(rewind) = &mut self.rewind {
+            rewind.clear();
+        }
+        self.video.show_error(None)?;
+        self.video.present(&self.cpu)?;
+        if self
+            .debugger
+            .as_ref()
+            .is_some_and(|debugger| debugger.paused())
+        {
+            self.debugger_stopped();
+        }
+        Ok(())
+    }
+
+    pub fn halted(&self) -> Option<CpuError> {
+        self.halted
+    }
+
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn quit(&self) -> bool {
+        self.quit
+    }
+
+    // run in real time until the input source asks to quit
+    pub fn run(&mut self) -> Result<(), String> {
+        let mut last_frame = Instant::now();
+        while !self.quit {
+            self.handle_input()?;
+            if self.quit {
+                break;
+            }
+
+            let now = Instant::now();
+            let frames = self.scheduler.advance(now - last_frame);
+            last_frame = now;
+            if frames == 0 {
+                std::thread::sleep(self.scheduler.time_until_next_frame());
+                continue;
+            }
+            self.run_frames(frames)?;
+        }
+        Ok(())
+    }
+
+    // poll the input source and act on everything it reports
+    pub fn handle_input(&mut self) -> Result<(), String> {
+        self.debug_commands();
+        self.reload()?;
+        for event in self.input.poll() {
+            match event {
+                InputEvent::Key { .. } if self.playing => {}
+                InputEvent::Key { key, pressed } => self.cpu.set_key(key, pressed),
+                // anything that moves the machine outside of the movie would desync it
+                InputEvent::Reset | InputEvent::LoadState(_) | InputEvent::Rewind(true)
+                    if self.movie.is_some() =>
+                {
+                    eprintln!("not while a movie is recording or playing....");
+                }
+                InputEvent::Quit => self.quit = true,
+                InputEvent::Reset => {
+                    self.cpu.reset();
+                    self.cpu
+                        .load_rom(self.rom.clone())
+                        .map_err(|e| e.to_string())?;
+                    self.halted = None;
+                    if let Some(rewind) = &mut self.rewind {
+                        rewind.clear();
+                    }
+                    self.video.show_error(None)?;
+                }
+                InputEvent::ToggleTurbo => {
+                    self.scheduler.toggle_turbo();
+                    let turbo = if self.scheduler.turbo() { "on" } else { "off" };
+                    self.status(&format!("turbo {}....", turbo));
+                }
+                InputEvent::ToggleMute => {
+                    self.muted = !self.muted;
+                    let sound = if self.muted { "muted" } else { "unmuted" };
+                    self.status(&format!("sound {}....", sound));
+                }
+                // a missing or bad slot is reported, the rom keeps running
+                InputEvent::SaveState(slot) => match self.save_state(slot) {
+                    Ok(()) => self.status(&format!("saved slot {}....", slot)),
+                    Err(e) => eprintln!("unable to save slot {}: {}", slot, e),
+                },
+                InputEvent::LoadState(slot) => match self.load_state(slot) {
+                    Ok(()) => {
+                        self.status(&format!("loaded slot {}....", slot));
+                        self.video.present(&self.cpu)?;
+                    }
+                    Err(e) => eprintln!("unable to load slot {}: {}", slot, e),
+                },
+                // without a buffer there is nothing to pop, rewinding would freeze the rom
+                InputEvent::Rewind(rewinding) => {
+                    self.rewinding = rewinding && self.rewind.is_some();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // run whole frames without looking at the clock, then present the last one
+    pub fn run_frames(&mut self, frames: u32) -> Result<(), String> {
+        for _ in 0..frames {
+            if self.rewinding {
+                self.rewind_frame()?;
+                continue;
+            }
+            if self.playing && self.playback.is_none() {
+                self.play_frame();
+            }
+            if self.halted.is_some() || self.playback.is_some() {
+                break;
+            }
+            if self
+                .debugger
+                .as_ref()
+                .is_some_and(|debugger| debugger.paused())
+            {
+                break;
+            }
+            if let Some(movie) = &mut self.movie {
+                if !self.playing {
+                    movie.frames.push(self.cpu.keypad_mask());
+                }
+            }
+            self.run_instructions()?;
+            self.cpu.tick_timers();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(self.cpu.save_state());
+            }
+        }
+
+        self.audio.set_pattern(self.cpu.audio_pattern());
+        self.audio
+            .set_beeping(self.cpu.sound_timer() > 0 && !self.muted && !self.rewinding);
+        self.video.present(&self.cpu)
+    }
+
+    // set the keypad for the next frame, or check the end state once the
+    // movie runs out
+    fn play_frame(&mut self) {
+        let Some(movie) = &self.movie else {
+            return;
+        };
+        match movie.frames.get(self.frame) {
+            Some(mask) if self.halted.is_none() => {
+                self.cpu.set_keypad_mask(*mask);
+                self.frame += 1;
+            }
+            _ => {
+                let result = movie.check(&self.cpu);
+                match &result {
+                    Ok(()) => self.status("playback finished, the end state matches...."),
+                    Err(e) => eprintln!("{}", e),
+                }
+                self.playback = Some(result);
+                self.quit = true;
+            }
+        }
+    }
+
+    // go back one frame, the oldest state stays on screen once the buffer is empty
+    fn rewind_frame(&mut self) -> Result<(), String> {
+        let Some(rewind) = &mut self.rewind else {
+            return Ok(());
+        };
+        // keep the oldest state so letting go resumes from it
+        if rewind.len() > 1 {
+            rewind.pop();
+        }
+        if let Some(state) = rewind.pop() {
+            self.cpu.load_state(&state)?;
+            rewind.push(state);
+            self.halted = None;
+        }
+        Ok(())
+    }
+
+    // the error, and the source line of the instruction at pc when known
+    fn halt_message(&self, error: &CpuError, pc: u16) -> String {
+        let line = self
+            .source_map
+            .as_ref()
+            .and_then(|source_map| source_map.describe(pc));
+        match line {
+            Some(line) => format!("cpu halted: {}\n  at {}", error, line),
+            None => format!("cpu halted: {}", error),
+        }
+    }
+
+    fn run_instructions(&mut self) -> Result<(), String> {
+        for _ in 0..self.scheduler.instructions_per_frame() {
+            let pc = self.cpu.pc();
+            if let Some(debugger) = &mut self.debugger {
+                if debugger.check(&self.cpu) {
+                    self.debugger_stopped();
+                    break;
+                }
+            }
+            match self.cpu.step() {
+                Ok(StepOutcome::Executed) => {
+                    if let Some(debugger) = &mut self.debugger {
+                        if let Some(report) = debugger.executed(&mut self.cpu) {
+                            match &mut self.dap {
+                                Some(session) => session.output(&report)?,
+                                None => print!("{}", report),
+                            }
+                            self.debugger_stopped();
+                            break;
+                        }
+                    }
+                }
+                // nothing changes until the next key event
+                Ok(StepOutcome::WaitingForKey) => break,
+                // the draw happens at the start of the next frame
+                Ok(StepOutcome::WaitingForVblank) => break,
+                Ok(StepOutcome::Exit) => {
+                    match &mut self.dap {
+                        Some(session) => session.exited()?,
+                        None => println!("rom exited...."),
+                    }
+                    if let Some(session) = &mut self.gdb {
+                        if let Err(e) = session.exited() {
+                            eprintln!("{}", e);
+                        }
+                    }
+                    self.quit = true;
+                    break;
+                }
+                Err(e) => {
+                    let message = self.halt_message(&e, pc);
+                    eprintln!("{}", message);
+                    if let Some(session) = &mut self.dap {
+                        session.output(&format!("{}\n", message))?;
+                    }
+                    self.video.show_error(Some(&e))?;
+                    self.halted = Some(e);
+                    if let Some(debugger) = &mut self.debugger {
+                        debugger.pause();
+                        self.debugger_stopped();
+                    }
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frontend::null::{NullAudio, NullInput, NullVideo};
+    use crate::reload::Build;
+
+    fn runner(
+        rom: Vec<u8>,
+        script: Vec<Vec<InputEvent>>,
+    ) -> Runner<NullVideo, NullAudio, NullInput> {
+        let mut cpu = Cpu::new();
+        cpu.load_rom(rom.clone()).unwrap();
+        Runner::new(
+            cpu,
+            Scheduler::new(2),
+            rom,
+            NullVideo::default(),
+            NullAudio::default(),
+            NullInput::new(script),
+        )
+    }
+
+    #[test]
+    fn test_run_frames() {
+        let mut runner = runner(
+            [
+                // address 0x200
+                // add 1 to v1
+                0x71, 0x01, // address 0x202
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            vec![],
+        );
+        runner.run_frames(3).unwrap();
+        // 2 instructions per frame, one add per frame
+        assert_eq!(runner.cpu.registers()[1], 3);
+        assert_eq!(runner.video.frames, 1);
+    }
+
+    #[test]
+    fn test_beeping_follows_sound_timer() {
+        let mut runner = runner([0x12, 0x00].to_vec(), vec![vec![InputEvent::ToggleMute]]);
+        runner.cpu.set_sound_timer(2);
+        runner.run_frames(1).unwrap();
+        assert!(runner.audio.beeping);
+        runner.run_frames(1).unwrap();
+        assert!(!runner.audio.beeping);
+
+        runner.cpu.set_sound_timer(2);
+        runner.handle_input().unwrap();
+        assert!(runner.muted());
+        runner.run_frames(1).unwrap();
+        assert!(!runner.audio.beeping);
+    }
+
+    #[test]
+    fn test_key_events() {
+        let mut runner = runner(
+            [
+                // address 0x200
+                // wait for a key press, store it in v1
+                0xf1, 0x0a,
+            ]
+            .to_vec(),
+            vec![vec![InputEvent::Key {
+                key: 0x7,
+                pressed: true,
+            }]],
+        );
+        runner.run_frames(1).unwrap();
+        assert_eq!(runner.cpu.pc(), 0x200);
+        runner.handle_input().unwrap();
+        assert!(runner.cpu.key(0x7));
+        runner.run_frames(1).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 0x7);
+    }
+
+    #[test]
+    fn test_halt_and_reset() {
+        let mut runner = runner(
+            [
+                // address 0x200
+                // not an instruction
+                0xff, 0xff,
+            ]
+            .to_vec(),
+            vec![vec![InputEvent::Reset]],
+        );
+        runner.run_frames(1).unwrap();
+        let error = CpuError::InvalidOpcode {
+            pc: 0x200,
+            opcode: 0xffff,
+        };
+        assert_eq!(runner.halted(), Some(error));
+        assert_eq!(runner.video.error, Some(error));
+        runner.handle_input().unwrap();
+        assert_eq!(runner.halted(), None);
+        assert_eq!(runner.video.error, None);
+        assert_eq!(runner.cpu.pc(), 0x200);
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut cpu = Cpu::with_platform(crate::Platform::SuperChip);
+        cpu.load_rom([0x00, 0xfd].to_vec()).unwrap();
+        let mut runner = Runner::new(
+            cpu,
+            Scheduler::new(2),
+            [0x00, 0xfd].to_vec(),
+            NullVideo::default(),
+            NullAudio::default(),
+            NullInput::default(),
+        );
+        runner.run().unwrap();
+        assert!(runner.quit());
+        assert_eq!(runner.cpu.pc(), 0x200);
+    }
+
+    #[test]
+    fn test_quit() {
+        let mut runner = runner([0x12, 0x00].to_vec(), vec![vec![InputEvent::Quit]]);
+        runner.run().unwrap();
+        assert!(runner.quit());
+    }
+
+    #[test]
+    fn test_save_state_slots() {
+        let mut runner = runner(
+            [
+                // address 0x200
+                // add 1 to v1
+                0x71, 0x01, // address 0x202
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            vec![
+                vec![InputEvent::SaveState(3)],
+                vec![InputEvent::LoadState(3)],
+                vec![InputEvent::LoadState(4)],
+            ],
+        );
+        let dir = std::env::temp_dir().join(format!("chip8-runner-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rom.ch8");
+        runner.set_state_path(path.to_str().unwrap());
+
+        runner.run_frames(2).unwrap();
+        runner.handle_input().unwrap();
+        assert!(dir.join("rom.ch8.state3").exists());
+        runner.run_frames(2).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 4);
+        runner.handle_input().unwrap();
+        assert_eq!(runner.cpu.registers()[1], 2);
+        // an empty slot leaves the cpu alone
+        runner.handle_input().unwrap();
+        assert_eq!(runner.cpu.registers()[1], 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut runner = runner(
+            [
+                // address 0x200
+                // add 1 to v1
+                0x71, 0x01, // address 0x202
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            vec![
+                vec![InputEvent::Rewind(true)],
+                vec![InputEvent::Rewind(false)],
+            ],
+        );
+        runner.set_rewind(Rewind::new(crate::rewind::DEFAULT_BUDGET, 3));
+        runner.run_frames(5).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 5);
+
+        runner.handle_input().unwrap();
+        assert!(runner.rewinding());
+        runner.run_frames(1).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 4);
+        // only 3 frames are kept, the oldest stays put
+        runner.run_frames(5).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 3);
+        assert_eq!(runner.video.frames, 3);
+
+        runner.handle_input().unwrap();
+        runner.run_frames(1).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 4);
+    }
+
+    #[test]
+    fn test_rewind_without_a_buffer() {
+        let mut runner = runner(
+            [
+                // address 0x200
+                // add 1 to v1
+                0x71, 0x01, // address 0x202
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            vec![vec![InputEvent::Rewind(true)]],
+        );
+        runner.handle_input().unwrap();
+        assert!(!runner.rewinding());
+        runner.run_frames(2).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 2);
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        let rom: Vec<u8> = [
+            // address 0x200
+            // put key 5 in v5
+            0x65, 0x05, // address 0x202
+            // skip the next instruction when the key in v5 is down
+            0xe5, 0x9e, // address 0x204
+            // jump to 0x202
+            0x12, 0x02, // address 0x206
+            // add a random number below 16 to v1, count the adds in v3
+            0xc2, 0x0f, 0x81, 0x24, 0x73, 0x01, // address 0x20c
+            // jump to 0x202
+            0x12, 0x02,
+        ]
+        .to_vec();
+        let press = |pressed| vec![InputEvent::Key { key: 0x5, pressed }];
+        let mut recording = runner(rom.clone(), vec![press(true), press(false)]);
+        recording.scheduler = Scheduler::new(4);
+        recording.record(Movie::new(&recording.cpu, 4).unwrap());
+        recording.run_frames(2).unwrap();
+        recording.handle_input().unwrap();
+        recording.run_frames(3).unwrap();
+        recording.handle_input().unwrap();
+        recording.run_frames(2).unwrap();
+        let movie = recording.finish_recording().unwrap();
+        assert_eq!(movie.frames, vec![0, 0, 0x20, 0x20, 0x20, 0, 0]);
+        assert_ne!(recording.cpu.registers()[3], 0);
+
+        let movie = Movie::parse(&movie.to_bytes()).unwrap();
+        let mut playback = runner(rom.clone(), vec![vec![], press(true)]);
+        playback.scheduler = Scheduler::new(4);
+        playback.play(movie.clone());
+        playback.run().unwrap();
+        assert_eq!(playback.playback(), Some(&Ok(())));
+        assert!(playback.quit());
+        assert_eq!(playback.cpu.registers(), recording.cpu.registers());
+
+        // a different run no longer matches
+        let mut desynced = runner(rom, vec![]);
+        desynced.scheduler = Scheduler::new(4);
+        desynced.play(movie);
+        desynced.cpu.set_seed(1);
+        desynced.run_frames(10).unwrap();
+        assert!(desynced.playback().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_debugger() {
+        let mut runner = runner(
+            [
+                // address 0x200
+                // add 1 to v1
+                0x71, 0x01, // address 0x202
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            vec![],
+        );
+        let (sender, commands) = std::sync::mpsc::channel();
+        runner.set_debugger(Debugger::new(), commands);
+        // paused, frames still present but nothing runs
+        runner.run_frames(2).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 0);
+        assert_eq!(runner.video.frames, 1);
+
+        sender.send("step".to_string()).unwrap();
+        runner.handle_input().unwrap();
+        runner.run_frames(2).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 1);
+        assert_eq!(runner.cpu.pc(), 0x202);
+
+        sender.send("break 200 if v1 == 3".to_string()).unwrap();
+        sender.send("continue".to_string()).unwrap();
+        runner.handle_input().unwrap();
+        runner.run_frames(10).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 3);
+        assert_eq!(runner.cpu.pc(), 0x200);
+        assert!(runner.debugger().unwrap().paused());
+
+        sender.send("quit".to_string()).unwrap();
+        runner.handle_input().unwrap();
+        assert!(runner.quit());
+    }
+
+    #[test]
+    fn test_gdb() {
+        use std::io::Read;
+        let mut runner = runner(
+            [
+                // address 0x200
+                // add 1 to v1
+                0x71, 0x01, // address 0x202
+                // jump to 0x200
+                0x12, 0x00,
+            ]
+            .to_vec(),
+            vec![],
+        );
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let mut client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let session = gdb::Session::new(listener.accept().unwrap().0).unwrap();
+        runner.set_gdb(Debugger::new(), session);
+        client
+            .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+            .unwrap();
+        let expect = |client: &mut std::net::TcpStream, expected: &[u8]| {
+            let mut received = vec![0x0; expected.len()];
+            client.read_exact(&mut received).unwrap();
+            assert_eq!(received, expected);
+        };
+
+        // break at 0x202 once v1 is 1, then continue
+        client.write_all(b"$Z0,202,2#a8$c#63").unwrap();
+        runner.handle_input().unwrap();
+        expect(&mut client, b"+$OK#9a+");
+        runner.run_frames(2).unwrap();
+        expect(&mut client, b"$S05#b8");
+        assert_eq!(runner.cpu.pc(), 0x202);
+        assert_eq!(runner.cpu.registers()[1], 1);
+        assert!(runner.debugger().unwrap().paused());
+
+        // single step onto the jump target
+        client.write_all(b"$s#73").unwrap();
+        runner.handle_input().unwrap();
+        runner.run_frames(1).unwrap();
+        expect(&mut client, b"+$S05#b8");
+        assert_eq!(runner.cpu.pc(), 0x200);
+
+        client.write_all(b"$k#6b").unwrap();
+        runner.handle_input().unwrap();
+        assert!(runner.quit());
+    }
+
+    #[test]
+    fn test_watch_reload() {
+        let rom = [
+            // address 0x200
+            // add 1 to v1
+            0x71, 0x01, // address 0x202
+            // jump to 0x200
+            0x12, 0x00,
+        ];
+        let dir = std::env::temp_dir().join(format!("chip8-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rom.ch8");
+        let write = |rom: &[u8], secs: u64| {
+            std::fs::write(&path, rom).unwrap();
+            let file = std::fs::File::options().write(true).open(&path).unwrap();
+            file.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+                .unwrap();
+        };
+        write(&rom, 1);
+        let path_str = path.to_str().unwrap();
+
+        let mut runner = runner(rom.to_vec(), vec![vec![InputEvent::SaveState(0)]]);
+        runner.set_state_path(path_str);
+        let build = Build::from_file(path_str, crate::Platform::Chip8).unwrap();
+        let mut watcher = Watcher::new(path_str, crate::Platform::Chip8, build);
+        watcher.set_interval(std::time::Duration::ZERO);
+        runner.set_watcher(watcher, true);
+
+        runner.run_frames(1).unwrap();
+        runner.handle_input().unwrap();
+        runner.run_frames(2).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 3);
+
+        // same length, the save state goes back on with the new code
+        write(&[0x72, 0x01, 0x12, 0x00], 2);
+        runner.handle_input().unwrap();
+        assert_eq!(runner.cpu.registers()[1], 1);
+        runner.run_frames(1).unwrap();
+        assert_eq!(runner.cpu.registers()[1], 1);
+        assert_eq!(runner.cpu.registers()[2], 1);
+
+        // a longer rom starts over
+        write(&[0x73, 0x01, 0x12, 0x00, 0x00, 0x00], 3);
+        runner.handle_input().unwrap();
+        assert_eq!(runner.cpu.registers()[1], 0);
+        assert_eq!(runner.cpu.pc(), 0x200);
+        runner.run_frames(1).unwrap();
+        assert_eq!(runner.cpu.registers()[3], 1);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_halt_message_names_the_source_line() {
+        let program = crate::asm::assemble("v1 := 1\n  0xff 0xff", crate::Platform::Chip8).unwrap();
+        let mut runner = runner(program.rom, vec![]);
+        runner.run_frames(1).unwrap();
+        let error = runner.halted().unwrap();
+        assert_eq!(
+            runner.halt_message(&error, 0x202),
+            "cpu halted: invalid instruction ffff at 0x202"
+        );
+        runner.set_source_map(program.source_map);
+        assert_eq!(
+            runner.halt_message(&error, 0x202),
+            "cpu halted: invalid instruction ffff at 0x202\n  at line 2: 0xff 0xff"
+        );
+    }
+
+    #[test]
+    fn test_run_source() {
+        let dir = std::env::temp_dir().join(format!("chip8-source-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("count.8o");
+        std::fs::write(
+            &path,
+            "\
+: main
+  v0 := 0
+  v1 := 0
+  loop
+    v0 += 1
+    if v0 >= 3 then v1 += 1
+    if v0 > 4 then v2 += 1
+    if v0 <= 1 then v3 += 1
+  while v0 < 6 again
+  v4 := 1
+: done
+  jump done
+",
+        )
+        .unwrap();
+
+        // assembled from the file, as the rom argument is
+        let build = Build::from_file(path.to_str().unwrap(), crate::Platform::Chip8).unwrap();
+        let mut runner = runner(build.rom, vec![]);
+        runner.set_source_map(build.source_map.unwrap());
+        runner.run_frames(50).unwrap();
+        assert!(runner.halted().is_none());
+        assert_eq!(runner.cpu.registers()[..5], [6, 4, 2, 1, 1]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
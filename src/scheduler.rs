@@ -0,0 +1,116 @@
+// fixed timestep scheduler
+// the emulated machine runs in 60 Hz frames, each frame executes a fixed
+// number of instructions and then ticks the timers once
+
+use std::time::Duration;
+
+pub const FRAME_RATE: u32 = 60;
+pub const FRAME_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+
+pub const DEFAULT_IPF: u32 = 11;
+
+// the most frames that are run to catch up after a slow frame
+// anything beyond this is dropped so a long stall can't snowball
+pub const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+pub struct Scheduler {
+    ipf: u32,
+    turbo: bool,
+    accumulator: Duration,
+}
+
+impl Scheduler {
+    pub fn new(ipf: u32) -> Self {
+        Self {
+            ipf: ipf.max(1),
+            turbo: false,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    // instructions per frame needed to run at roughly clock_hz instructions a second
+    pub fn from_clock_hz(clock_hz: u32) -> Self {
+        let ipf = (clock_hz + FRAME_RATE / 2) / FRAME_RATE;
+        Self::new(ipf)
+    }
+
+    pub fn instructions_per_frame(&self) -> u32 {
+        self.ipf
+    }
+
+    pub fn turbo(&self) -> bool {
+        self.turbo
+    }
+
+    pub fn toggle_turbo(&mut self) {
+        self.turbo = !self.turbo;
+        self.accumulator = Duration::ZERO;
+    }
+
+    // add the wall clock time since the last call and return how many
+    // frames should be run now
+    // in turbo mode a frame is always due, the wall clock is ignored
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        if self.turbo {
+            return 1;
+        }
+        self.accumulator += elapsed;
+        let mut frames = 0;
+        while self.accumulator >= FRAME_PERIOD {
+            self.accumulator -= FRAME_PERIOD;
+            frames += 1;
+        }
+        if frames > MAX_CATCH_UP_FRAMES {
+            frames = MAX_CATCH_UP_FRAMES;
+            self.accumulator = Duration::ZERO;
+        }
+        frames
+    }
+
+    // how long the caller can sleep before the next frame is due
+    pub fn time_until_next_frame(&self) -> Duration {
+        if self.turbo {
+            return Duration::ZERO;
+        }
+        FRAME_PERIOD.saturating_sub(self.accumulator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_clock_hz() {
+        assert_eq!(Scheduler::from_clock_hz(600).instructions_per_frame(), 10);
+        assert_eq!(Scheduler::from_clock_hz(700).instructions_per_frame(), 12);
+        assert_eq!(Scheduler::from_clock_hz(1).instructions_per_frame(), 1);
+    }
+
+    #[test]
+    fn test_advance_accumulates() {
+        let mut scheduler = Scheduler::new(10);
+        assert_eq!(scheduler.advance(FRAME_PERIOD / 2), 0);
+        assert_eq!(scheduler.advance(FRAME_PERIOD / 2), 1);
+        assert_eq!(scheduler.advance(FRAME_PERIOD * 2), 2);
+    }
+
+    #[test]
+    fn test_advance_caps_catch_up() {
+        let mut scheduler = Scheduler::new(10);
+        assert_eq!(scheduler.advance(FRAME_PERIOD * 100), MAX_CATCH_UP_FRAMES);
+        // the backlog is dropped rather than carried over
+        assert_eq!(scheduler.advance(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn test_turbo() {
+        let mut scheduler = Scheduler::new(10);
+        scheduler.toggle_turbo();
+        assert!(scheduler.turbo());
+        assert_eq!(scheduler.advance(Duration::ZERO), 1);
+        assert_eq!(scheduler.time_until_next_frame(), Duration::ZERO);
+        scheduler.toggle_turbo();
+        assert_eq!(scheduler.advance(Duration::ZERO), 0);
+    }
+}